@@ -0,0 +1,42 @@
+use serde::Deserialize;
+
+/// A websocket message as API Gateway passes it to a lambda.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Event {
+    pub request_context: RequestContext,
+    pub body: String, // parse this into json
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestContext {
+    pub connection_id: String,
+    pub domain_name: String,
+    pub stage: String,
+}
+
+impl RequestContext {
+    /// The endpoint to post messages back to connections through.
+    pub fn endpoint(&self) -> String {
+        format!("https://{}/{}", self.domain_name, self.stage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_endpoint() {
+        assert_eq!(
+            RequestContext {
+                connection_id: "Wt8z-cgAIAMCIdw=".into(),
+                domain_name: "xxx.execute-api.ca-central-1.amazonaws.com".into(),
+                stage: "dev".into()
+            }
+            .endpoint(),
+            "https://xxx.execute-api.ca-central-1.amazonaws.com/dev"
+        )
+    }
+}
@@ -0,0 +1,494 @@
+use messages::{ErrorKind, Message};
+use nanoid::nanoid;
+
+use crate::handlers;
+use crate::lobby::{Lobby, LobbyService, LobbyServiceError, Player};
+use crate::moderation::WordFilter;
+use crate::router::Context;
+
+/// Keep a chat message with the lobby and send it to every player in it who is still connected,
+/// or only the ones it is for, including the sender so they know it arrived.
+///
+/// Messages the lobby's moderation refuses are reported back to the sender only, and ones starting
+/// with a `/` are run as commands instead.
+pub async fn send(
+    ctx: &Context<'_>,
+    message: messages::LobbyMessage,
+) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
+    if let Some(command) = message.body.strip_prefix('/') {
+        return handlers::commands::run(ctx, &message.lobby_code, command).await;
+    }
+    let (lobby, stored) = LobbyService::post_message(
+        ctx.repo,
+        &ctx.now,
+        &message.lobby_code,
+        ctx.connection_id,
+        message.body,
+        message.to.as_ref(),
+        &WordFilter::from_env(),
+    )
+    .await?;
+    let response: messages::LobbyMessageResponse = (&stored).into();
+
+    for player in lobby
+        .players
+        .iter()
+        .filter(|p| !p.away && stored.is_visible_to(p))
+    {
+        // Only the sender has a pending copy to match up with the client id.
+        let client_id = if &player.connection_id == ctx.connection_id {
+            message.client_id.clone()
+        } else {
+            None
+        };
+        ctx.transport
+            .post_to_connection(
+                &player.connection_id,
+                Message::LobbyMessageResponse(messages::LobbyMessageResponse {
+                    client_id,
+                    ..response.clone()
+                }),
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Show a reaction to everyone in the lobby, next to the seat or chat message it is for, or only
+/// to the players who can see that chat message.
+///
+/// Reactions are moderated like chat messages, so they count towards the same rate limit.
+pub async fn react(
+    ctx: &Context<'_>,
+    message: messages::LobbyReaction,
+) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
+    let lobby =
+        LobbyService::moderate(ctx.repo, &ctx.now, &message.lobby_code, ctx.connection_id).await?;
+    let sender = lobby.player(ctx.connection_id).ok_or_else(|| {
+        LobbyServiceError::new(ErrorKind::PlayerNotFound, "You are not in this lobby")
+    })?;
+    let target = match &message.message_id {
+        Some(id) => Some(
+            lobby
+                .chat
+                .iter()
+                .find(|m| &m.id == id && m.is_visible_to(sender))
+                .ok_or_else(|| {
+                    LobbyServiceError::new(
+                        ErrorKind::InvalidAction,
+                        "That chat message is no longer kept",
+                    )
+                })?,
+        ),
+        None => None,
+    };
+
+    let response = Message::LobbyReactionResponse(messages::LobbyReactionResponse {
+        id: nanoid!(12),
+        name: sender.name.clone(),
+        seat: lobby.seat_of(ctx.connection_id),
+        reaction: message.reaction,
+        message_id: message.message_id,
+    });
+    // A reaction to a private message would give it away to anyone else who saw the reaction.
+    let can_see = |player: &Player| match target {
+        Some(m) => m.is_visible_to(player),
+        None => true,
+    };
+    for player in lobby.players.iter().filter(|p| !p.away && can_see(p)) {
+        ctx.transport
+            .post_to_connection(&player.connection_id, response.clone())
+            .await?;
+    }
+    Ok(())
+}
+
+/// Say something in the chat of every player in the lobby, keeping it in the chat history and
+/// sending it to the ones who are still connected.
+pub async fn announce(
+    ctx: &Context<'_>,
+    lobby: &Lobby,
+    body: String,
+) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
+    let (lobby, stored) =
+        LobbyService::post_system_message(ctx.repo, &ctx.now, &lobby.id, body, None).await?;
+    let message = Message::SystemMessage((&stored).into());
+    for player in lobby.players.iter().filter(|p| !p.away) {
+        ctx.transport
+            .post_to_connection(&player.connection_id, message.clone())
+            .await?;
+    }
+    Ok(())
+}
+
+/// Say something in the chat of only the player on this connection, keeping it in their chat
+/// history.
+pub async fn reply(
+    ctx: &Context<'_>,
+    lobby: &Lobby,
+    body: String,
+) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
+    let (_, stored) = LobbyService::post_system_message(
+        ctx.repo,
+        &ctx.now,
+        &lobby.id,
+        body,
+        Some(ctx.connection_id),
+    )
+    .await?;
+    ctx.transport
+        .post_to_connection(ctx.connection_id, Message::SystemMessage((&stored).into()))
+        .await
+}
+
+/// Send the player on this connection the page of chat messages before the one they asked about.
+pub async fn history(
+    ctx: &Context<'_>,
+    message: messages::LobbyMessageHistory,
+) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
+    let lobby = LobbyService::get(ctx.repo, &message.lobby_code).await?;
+    let viewer = lobby.player(ctx.connection_id).ok_or_else(|| {
+        LobbyServiceError::new(ErrorKind::PlayerNotFound, "You are not in this lobby")
+    })?;
+
+    let (page, more) = lobby.chat_page(&ctx.now, viewer, message.before.as_ref());
+    ctx.transport
+        .post_to_connection(
+            ctx.connection_id,
+            Message::LobbyMessageHistoryResponse(messages::LobbyMessageHistoryResponse {
+                lobby_code: lobby.id.clone(),
+                messages: page.iter().map(|m| m.into()).collect(),
+                more,
+            }),
+        )
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+    use crate::lobby::{InMemoryLobbyRepo, LobbyCodes};
+    use crate::transport::RecordingTransport;
+
+    #[tokio::test]
+    async fn chat_is_posted_to_everyone_and_echoed_to_the_sender() {
+        let repo = InMemoryLobbyRepo::new();
+        let transport = RecordingTransport::new();
+        let host = "host-conn".to_string();
+        let amigo = "amigo-conn".to_string();
+        let lobby = LobbyService::create(
+            &repo,
+            &Utc::now(),
+            &"Host".to_string(),
+            &host,
+            &LobbyCodes::default(),
+        )
+        .await
+        .unwrap();
+        LobbyService::join(&repo, &Utc::now(), &lobby.id, &"Amigo".to_string(), &amigo)
+            .await
+            .unwrap();
+
+        let ctx = Context {
+            repo: &repo,
+            transport: &transport,
+            connection_id: &host,
+            now: Utc::now(),
+        };
+        send(
+            &ctx,
+            messages::LobbyMessage {
+                lobby_code: lobby.id.clone(),
+                body: "howdy".to_string(),
+                client_id: Some("pending-1".to_string()),
+                to: None,
+            },
+        )
+        .await
+        .expect("failed to send message");
+
+        let echoed = match &transport.messages_for(&host)[..] {
+            [Message::LobbyMessageResponse(response)] => response.clone(),
+            other => panic!("unexpected messages {:?}", other),
+        };
+        assert_eq!(echoed.name, "Host");
+        assert_eq!(echoed.body, "howdy");
+        assert_eq!(echoed.sent_at, ctx.now);
+        assert_eq!(echoed.client_id, Some("pending-1".to_string()));
+        assert_eq!(
+            transport.messages_for(&amigo),
+            vec![Message::LobbyMessageResponse(
+                messages::LobbyMessageResponse {
+                    client_id: None,
+                    ..echoed.clone()
+                }
+            )]
+        );
+
+        history(
+            &Context {
+                connection_id: &amigo,
+                ..ctx
+            },
+            messages::LobbyMessageHistory {
+                lobby_code: lobby.id.clone(),
+                before: None,
+            },
+        )
+        .await
+        .expect("failed to get history");
+        match transport.messages_for(&amigo).last() {
+            Some(Message::LobbyMessageHistoryResponse(response)) => {
+                assert_eq!(response.messages.len(), 1);
+                assert_eq!(response.messages[0].id, echoed.id);
+                assert_eq!(response.messages[0].client_id, None);
+                assert!(!response.more);
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn reactions_are_shown_to_everyone_with_the_seat() {
+        let repo = InMemoryLobbyRepo::new();
+        let transport = RecordingTransport::new();
+        let host = "host-conn".to_string();
+        let amigo = "amigo-conn".to_string();
+        let lobby = LobbyService::create(
+            &repo,
+            &Utc::now(),
+            &"Host".to_string(),
+            &host,
+            &LobbyCodes::default(),
+        )
+        .await
+        .unwrap();
+        LobbyService::join(&repo, &Utc::now(), &lobby.id, &"Amigo".to_string(), &amigo)
+            .await
+            .unwrap();
+
+        let ctx = Context {
+            repo: &repo,
+            transport: &transport,
+            connection_id: &amigo,
+            now: Utc::now(),
+        };
+        react(
+            &ctx,
+            messages::LobbyReaction {
+                lobby_code: lobby.id.clone(),
+                reaction: messages::Reaction::Ouch,
+                message_id: None,
+            },
+        )
+        .await
+        .expect("failed to react");
+
+        match &transport.messages_for(&host)[..] {
+            [Message::LobbyReactionResponse(response)] => {
+                assert_eq!(response.name, "Amigo");
+                assert_eq!(response.seat, Some(1));
+                assert_eq!(response.reaction, messages::Reaction::Ouch);
+            }
+            other => panic!("unexpected messages {:?}", other),
+        }
+        let unknown_message = react(
+            &ctx,
+            messages::LobbyReaction {
+                lobby_code: lobby.id.clone(),
+                reaction: messages::Reaction::Gg,
+                message_id: Some("gone".to_string()),
+            },
+        )
+        .await;
+        assert!(unknown_message.is_err());
+    }
+
+    #[tokio::test]
+    async fn reactions_are_moderated_like_chat() {
+        let repo = InMemoryLobbyRepo::new();
+        let transport = RecordingTransport::new();
+        let host = "host-conn".to_string();
+        let amigo = "amigo-conn".to_string();
+        let lobby = LobbyService::create(
+            &repo,
+            &Utc::now(),
+            &"Host".to_string(),
+            &host,
+            &LobbyCodes::default(),
+        )
+        .await
+        .unwrap();
+        LobbyService::join(&repo, &Utc::now(), &lobby.id, &"Amigo".to_string(), &amigo)
+            .await
+            .unwrap();
+        LobbyService::mute_player(
+            &repo,
+            &Utc::now(),
+            &lobby.id,
+            &host,
+            &"Amigo".to_string(),
+            true,
+        )
+        .await
+        .unwrap();
+        let ctx = Context {
+            repo: &repo,
+            transport: &transport,
+            connection_id: &amigo,
+            now: Utc::now(),
+        };
+        let reaction = || messages::LobbyReaction {
+            lobby_code: lobby.id.clone(),
+            reaction: messages::Reaction::Ouch,
+            message_id: None,
+        };
+
+        let error = react(&ctx, reaction()).await.unwrap_err();
+        let error = error.downcast_ref::<LobbyServiceError>().unwrap();
+        assert_eq!(error.to_response().kind, ErrorKind::Muted);
+        assert_eq!(transport.messages_for(&host), vec![]);
+
+        let host_ctx = Context {
+            connection_id: &host,
+            ..ctx
+        };
+        for _ in 0..crate::moderation::CHAT_RATE_LIMIT {
+            react(&host_ctx, reaction()).await.unwrap();
+        }
+        let error = react(&host_ctx, reaction()).await.unwrap_err();
+        let error = error.downcast_ref::<LobbyServiceError>().unwrap();
+        assert_eq!(error.to_response().kind, ErrorKind::RateLimited);
+        assert_eq!(
+            transport.messages_for(&amigo).len(),
+            crate::moderation::CHAT_RATE_LIMIT
+        );
+    }
+
+    #[tokio::test]
+    async fn whispers_only_reach_their_recipient() {
+        let repo = InMemoryLobbyRepo::new();
+        let transport = RecordingTransport::new();
+        let connections: Vec<String> = (0..3).map(|i| format!("conn-{}", i)).collect();
+        let lobby = LobbyService::create(
+            &repo,
+            &Utc::now(),
+            &"Host".to_string(),
+            &connections[0],
+            &LobbyCodes::default(),
+        )
+        .await
+        .unwrap();
+        for (name, connection_id) in ["Amigo", "Other"].iter().zip(&connections[1..]) {
+            LobbyService::join(
+                &repo,
+                &Utc::now(),
+                &lobby.id,
+                &name.to_string(),
+                connection_id,
+            )
+            .await
+            .unwrap();
+        }
+        let ctx = Context {
+            repo: &repo,
+            transport: &transport,
+            connection_id: &connections[0],
+            now: Utc::now(),
+        };
+        let whisper = |name: &str| messages::LobbyMessage {
+            lobby_code: lobby.id.clone(),
+            body: "psst".to_string(),
+            client_id: None,
+            to: Some(messages::Recipient::Player {
+                name: name.to_string(),
+            }),
+        };
+
+        send(&ctx, whisper("Amigo"))
+            .await
+            .expect("failed to whisper");
+        assert!(send(&ctx, whisper("Nobody")).await.is_err());
+
+        assert_eq!(transport.messages_for(&connections[0]).len(), 1);
+        assert_eq!(transport.messages_for(&connections[1]).len(), 1);
+        assert_eq!(transport.messages_for(&connections[2]), vec![]);
+        let stored = LobbyService::get(&repo, &lobby.id).await.unwrap();
+        let (seen, _) = stored.chat_page(&ctx.now, &stored.players[2], None);
+        assert_eq!(seen, vec![]);
+
+        // Reacting to the whisper doesn't tell anyone else about it either.
+        react(
+            &Context {
+                connection_id: &connections[1],
+                ..ctx
+            },
+            messages::LobbyReaction {
+                lobby_code: lobby.id.clone(),
+                reaction: messages::Reaction::Nice,
+                message_id: Some(stored.chat[0].id.clone()),
+            },
+        )
+        .await
+        .expect("failed to react");
+        assert_eq!(transport.messages_for(&connections[0]).len(), 2);
+        assert_eq!(transport.messages_for(&connections[2]), vec![]);
+
+        // Whoever takes the name next is somebody else, and must not see it either.
+        LobbyService::leave(&repo, &Utc::now(), &lobby.id, &connections[1])
+            .await
+            .unwrap();
+        let stored = LobbyService::join(
+            &repo,
+            &Utc::now(),
+            &lobby.id,
+            &"Amigo".to_string(),
+            &"conn-3".to_string(),
+        )
+        .await
+        .unwrap();
+        let newcomer = stored.player(&"conn-3".to_string()).unwrap();
+        assert_eq!(newcomer.name, "Amigo");
+        let (seen, _) = stored.chat_page(&ctx.now, newcomer, None);
+        assert_eq!(seen, vec![]);
+    }
+
+    #[tokio::test]
+    async fn chat_from_outside_the_lobby_is_rejected() {
+        let repo = InMemoryLobbyRepo::new();
+        let transport = RecordingTransport::new();
+        let lobby = LobbyService::create(
+            &repo,
+            &Utc::now(),
+            &"Host".to_string(),
+            &"host-conn".to_string(),
+            &LobbyCodes::default(),
+        )
+        .await
+        .unwrap();
+
+        let stranger = "stranger-conn".to_string();
+        let ctx = Context {
+            repo: &repo,
+            transport: &transport,
+            connection_id: &stranger,
+            now: Utc::now(),
+        };
+        let result = send(
+            &ctx,
+            messages::LobbyMessage {
+                lobby_code: lobby.id.clone(),
+                body: "let me in".to_string(),
+                client_id: None,
+                to: None,
+            },
+        )
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(transport.messages_for("host-conn"), vec![]);
+    }
+}
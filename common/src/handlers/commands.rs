@@ -0,0 +1,214 @@
+use messages::ErrorKind;
+use rand::Rng;
+
+use crate::handlers;
+use crate::lobby::{GameState, LobbyService, LobbyServiceError};
+use crate::router::Context;
+
+const RULES: &str = "Pass three cards before each hand, except every fourth. The two of clubs \
+                     leads, follow suit if you can, and hearts can't be led until one has been \
+                     played. Each heart is a point and the queen of spades is 13, unless one \
+                     player takes them all and shoots the moon. Lowest score when someone \
+                     reaches 100 wins.";
+
+/// The sides of the die `/roll` throws when it isn't given a number.
+const DEFAULT_ROLL: u32 = 6;
+
+/// Run a chat command, which is the chat message without its leading `/`.
+///
+/// Commands are moderated like the rest of the chat, so muted players can't use them and they
+/// count towards the rate limit.
+pub async fn run(
+    ctx: &Context<'_>,
+    lobby_code: &String,
+    command: &str,
+) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
+    let lobby = LobbyService::moderate(ctx.repo, &ctx.now, lobby_code, ctx.connection_id).await?;
+    let sender = lobby.player(ctx.connection_id).ok_or_else(|| {
+        LobbyServiceError::new(ErrorKind::PlayerNotFound, "You are not in this lobby")
+    })?;
+
+    let mut words = command.split_whitespace();
+    match words.next().unwrap_or("") {
+        "rules" => handlers::chat::reply(ctx, &lobby, RULES.to_string()).await,
+        "score" => {
+            let scores = match &lobby.game {
+                Some(GameState(game)) => game
+                    .scores
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(seat, score)| {
+                        let player = lobby.seated(seat)?;
+                        Some(format!("{} {}", player.name, score))
+                    })
+                    .collect::<Vec<String>>()
+                    .join(", "),
+                None => "No game has been started yet".to_string(),
+            };
+            handlers::chat::reply(ctx, &lobby, scores).await
+        }
+        "kick" => {
+            let name = words.collect::<Vec<&str>>().join(" ");
+            handlers::lobby::kick(ctx, lobby_code, &name).await
+        }
+        "ready" => handlers::lobby::ready(ctx, lobby_code, !sender.ready).await,
+        "roll" => {
+            let sides = words
+                .next()
+                .and_then(|sides| sides.parse::<u32>().ok())
+                .filter(|&sides| sides > 1)
+                .unwrap_or(DEFAULT_ROLL);
+            let roll = rand::thread_rng().gen_range(0, sides) + 1;
+            let body = format!("{} rolled {} (1-{})", sender.name, roll, sides);
+            handlers::chat::announce(ctx, &lobby, body).await
+        }
+        other => Err(LobbyServiceError::new(
+            ErrorKind::InvalidMessage,
+            &format!(
+                "There is no /{} command, try /rules, /score, /kick, /ready or /roll",
+                other
+            ),
+        )
+        .into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use messages::Message;
+
+    use super::*;
+    use crate::lobby::{InMemoryLobbyRepo, LobbyCodes};
+    use crate::transport::RecordingTransport;
+
+    fn system_bodies(messages: Vec<Message>) -> Vec<String> {
+        messages
+            .into_iter()
+            .filter_map(|message| match message {
+                Message::SystemMessage(m) => Some(m.body),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn commands_answer_in_the_chat() {
+        let repo = InMemoryLobbyRepo::new();
+        let transport = RecordingTransport::new();
+        let host = "host-conn".to_string();
+        let amigo = "amigo-conn".to_string();
+        let lobby = LobbyService::create(
+            &repo,
+            &Utc::now(),
+            &"Host".to_string(),
+            &host,
+            &LobbyCodes::default(),
+        )
+        .await
+        .unwrap();
+        LobbyService::join(&repo, &Utc::now(), &lobby.id, &"Amigo".to_string(), &amigo)
+            .await
+            .unwrap();
+        let ctx = Context {
+            repo: &repo,
+            transport: &transport,
+            connection_id: &host,
+            now: Utc::now(),
+        };
+
+        run(&ctx, &lobby.id, "score").await.unwrap();
+        run(&ctx, &lobby.id, "ready").await.unwrap();
+        assert_eq!(
+            system_bodies(transport.messages_for(&host)),
+            vec!["No game has been started yet", "Host is ready"]
+        );
+        assert_eq!(
+            system_bodies(transport.messages_for(&amigo)),
+            vec!["Host is ready"]
+        );
+        // They are kept in the chat history too, where replies are only seen by who asked.
+        let stored = LobbyService::get(&repo, &lobby.id).await.unwrap();
+        let history = |connection_id: &String| {
+            let viewer = stored.player(connection_id).unwrap();
+            let (page, _) = stored.chat_page(&ctx.now, viewer, None);
+            page.into_iter()
+                .filter(|m| m.system)
+                .map(|m| m.body)
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(
+            history(&host),
+            vec!["No game has been started yet", "Host is ready"]
+        );
+        assert_eq!(history(&amigo), vec!["Host is ready"]);
+
+        assert!(run(&ctx, &lobby.id, "dance").await.is_err());
+        let amigo_ctx = Context {
+            connection_id: &amigo,
+            ..ctx
+        };
+        assert!(run(&amigo_ctx, &lobby.id, "kick Host").await.is_err());
+
+        run(&ctx, &lobby.id, "kick Amigo").await.unwrap();
+        let stored = LobbyService::get(&repo, &lobby.id).await.unwrap();
+        assert_eq!(stored.players.len(), 1);
+        match transport.messages_for(&amigo).last() {
+            Some(Message::LobbyUpdate(update)) => assert_eq!(update.lobby.players.len(), 1),
+            other => panic!("unexpected message {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn commands_are_moderated_like_chat() {
+        let repo = InMemoryLobbyRepo::new();
+        let transport = RecordingTransport::new();
+        let host = "host-conn".to_string();
+        let amigo = "amigo-conn".to_string();
+        let lobby = LobbyService::create(
+            &repo,
+            &Utc::now(),
+            &"Host".to_string(),
+            &host,
+            &LobbyCodes::default(),
+        )
+        .await
+        .unwrap();
+        LobbyService::join(&repo, &Utc::now(), &lobby.id, &"Amigo".to_string(), &amigo)
+            .await
+            .unwrap();
+        LobbyService::mute_player(
+            &repo,
+            &Utc::now(),
+            &lobby.id,
+            &host,
+            &"Amigo".to_string(),
+            true,
+        )
+        .await
+        .unwrap();
+        let ctx = Context {
+            repo: &repo,
+            transport: &transport,
+            connection_id: &amigo,
+            now: Utc::now(),
+        };
+
+        let error = run(&ctx, &lobby.id, "roll").await.unwrap_err();
+        let error = error.downcast_ref::<LobbyServiceError>().unwrap();
+        assert_eq!(error.to_response().kind, ErrorKind::Muted);
+        assert_eq!(transport.messages_for(&host), vec![]);
+
+        let host_ctx = Context {
+            connection_id: &host,
+            ..ctx
+        };
+        for _ in 0..crate::moderation::CHAT_RATE_LIMIT {
+            run(&host_ctx, &lobby.id, "roll").await.unwrap();
+        }
+        let error = run(&host_ctx, &lobby.id, "roll").await.unwrap_err();
+        let error = error.downcast_ref::<LobbyServiceError>().unwrap();
+        assert_eq!(error.to_response().kind, ErrorKind::RateLimited);
+    }
+}
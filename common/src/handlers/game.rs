@@ -0,0 +1,275 @@
+use game::{Played, Seat, Suit};
+use messages::Message;
+
+use crate::handlers;
+use crate::lobby::{GameState, Lobby, LobbyService};
+use crate::router::Context;
+
+pub async fn start(
+    ctx: &Context<'_>,
+    message: messages::GameActionStart,
+) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
+    let lobby =
+        LobbyService::start_game(ctx.repo, &ctx.now, &message.lobby_code, ctx.connection_id)
+            .await?;
+    started(ctx, &lobby).await
+}
+
+/// Show everyone a game that was just dealt, and that nobody is ready for the next one yet.
+pub async fn started(
+    ctx: &Context<'_>,
+    lobby: &Lobby,
+) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
+    handlers::lobby::broadcast_lobby(ctx, lobby).await?;
+    broadcast(ctx, lobby).await?;
+    broadcast_scores(ctx, lobby).await
+}
+
+pub async fn play(
+    ctx: &Context<'_>,
+    message: messages::GameActionPlay,
+) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
+    let (lobby, played) = LobbyService::play_card(
+        ctx.repo,
+        &ctx.now,
+        &message.lobby_code,
+        ctx.connection_id,
+        message.card,
+    )
+    .await?;
+    broadcast(ctx, &lobby).await?;
+    if let Some(GameState(game)) = &lobby.game {
+        if played != Played::Hand && broke_hearts(game, message.card) {
+            handlers::chat::announce(ctx, &lobby, "Hearts are broken".to_string()).await?;
+        }
+        if played == Played::Hand {
+            let moon = game.history.last().and_then(|hand| hand.moon);
+            if let Some(player) = moon.and_then(|seat| lobby.seated(seat)) {
+                let body = format!("{} shot the moon!", player.name);
+                handlers::chat::announce(ctx, &lobby, body).await?;
+            }
+            broadcast_scores(ctx, &lobby).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether `card`, which was just played, is the first heart of the hand.
+fn broke_hearts(
+    game: &game::Game,
+    card: game::Card,
+) -> bool {
+    let hearts_played = game
+        .trick
+        .iter()
+        .map(|played| &played.card)
+        .chain(game.taken.iter().flatten())
+        .filter(|c| c.suit == Suit::Hearts)
+        .count();
+    card.suit == Suit::Hearts && hearts_played == 1
+}
+
+pub async fn pass(
+    ctx: &Context<'_>,
+    message: messages::GameActionPass,
+) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
+    let lobby = LobbyService::pass_cards(
+        ctx.repo,
+        &ctx.now,
+        &message.lobby_code,
+        ctx.connection_id,
+        message.cards,
+    )
+    .await?;
+    broadcast(ctx, &lobby).await
+}
+
+/// Send every player at the table who is still connected their own view of the game.
+async fn broadcast(
+    ctx: &Context<'_>,
+    lobby: &Lobby,
+) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
+    if let Some(GameState(game)) = &lobby.game {
+        for seat in 0..game::PLAYERS {
+            if let Some(player) = lobby.seated(seat).filter(|p| !p.away) {
+                ctx.transport
+                    .post_to_connection(
+                        &player.connection_id,
+                        Message::GameUpdate(messages::GameUpdate {
+                            game: view(lobby, game, seat),
+                        }),
+                    )
+                    .await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Send everyone still connected to the lobby the scores of every hand so far.
+async fn broadcast_scores(
+    ctx: &Context<'_>,
+    lobby: &Lobby,
+) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
+    if let Some(GameState(game)) = &lobby.game {
+        let scores = scores(lobby, game);
+        for player in lobby.players.iter().filter(|p| !p.away) {
+            ctx.transport
+                .post_to_connection(&player.connection_id, Message::ScoreUpdate(scores.clone()))
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Send one connection everything it needs to show the game, like after it reconnects.
+pub async fn send_state(
+    ctx: &Context<'_>,
+    lobby: &Lobby,
+    connection_id: &String,
+) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
+    if let Some(GameState(game)) = &lobby.game {
+        if let Some(seat) = lobby.seat_of(connection_id) {
+            ctx.transport
+                .post_to_connection(
+                    connection_id,
+                    Message::GameUpdate(messages::GameUpdate {
+                        game: view(lobby, game, seat),
+                    }),
+                )
+                .await?;
+        }
+        ctx.transport
+            .post_to_connection(connection_id, Message::ScoreUpdate(scores(lobby, game)))
+            .await?;
+    }
+    Ok(())
+}
+
+fn scores(
+    lobby: &Lobby,
+    game: &game::Game,
+) -> messages::ScoreUpdate {
+    messages::ScoreUpdate {
+        lobby_code: lobby.id.clone(),
+        players: (0..game::PLAYERS)
+            .map(|seat| seat_name(lobby, seat))
+            .collect(),
+        hands: game.history.clone(),
+        totals: game.scores.clone(),
+        finished: game.is_finished(),
+    }
+}
+
+/// The name of the player in `seat`, which is only empty if they were removed mid-game.
+fn seat_name(
+    lobby: &Lobby,
+    seat: Seat,
+) -> String {
+    lobby
+        .seated(seat)
+        .map(|p| p.name.clone())
+        .unwrap_or_default()
+}
+
+/// The game as the player in `seat` is allowed to see it.
+pub fn view(
+    lobby: &Lobby,
+    game: &game::Game,
+    seat: Seat,
+) -> messages::GameView {
+    messages::GameView {
+        seat,
+        seats: (0..game::PLAYERS)
+            .map(|i| messages::SeatView {
+                name: seat_name(lobby, i),
+                cards_in_hand: game.hands[i].len(),
+                score: game.scores[i],
+                passed: game.has_passed(i),
+            })
+            .collect(),
+        hand: game.hands[seat].clone(),
+        trick: game.trick.clone(),
+        last_trick: game.last_trick.clone(),
+        turn: game.turn,
+        first_trick: game.is_first_trick(),
+        hearts_broken: game.hearts_broken,
+        hand_number: game.hand_number,
+        phase: game.phase,
+        pass_direction: game.pass_direction(),
+        passed: game.passes[seat].clone(),
+        received: game.received[seat].clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+    use crate::lobby::{InMemoryLobbyRepo, LobbyCodes};
+    use crate::transport::RecordingTransport;
+
+    #[tokio::test]
+    async fn start_sends_each_player_only_their_own_hand() {
+        let repo = InMemoryLobbyRepo::new();
+        let transport = RecordingTransport::new();
+        let connections: Vec<String> = (0..4).map(|i| format!("conn-{}", i)).collect();
+        let lobby = LobbyService::create(
+            &repo,
+            &Utc::now(),
+            &"Host".to_string(),
+            &connections[0],
+            &LobbyCodes::default(),
+        )
+        .await
+        .unwrap();
+        for (i, connection_id) in connections.iter().enumerate().skip(1) {
+            LobbyService::join(
+                &repo,
+                &Utc::now(),
+                &lobby.id,
+                &format!("Player {}", i),
+                connection_id,
+            )
+            .await
+            .unwrap();
+        }
+        for connection_id in connections.iter() {
+            LobbyService::set_ready(&repo, &Utc::now(), &lobby.id, connection_id, true)
+                .await
+                .unwrap();
+        }
+
+        let ctx = Context {
+            repo: &repo,
+            transport: &transport,
+            connection_id: &connections[0],
+            now: Utc::now(),
+        };
+        start(
+            &ctx,
+            messages::GameActionStart {
+                lobby_code: lobby.id.clone(),
+            },
+        )
+        .await
+        .expect("failed to start game");
+
+        let lobby = LobbyService::get(&repo, &lobby.id).await.unwrap();
+        let game = &lobby.game.as_ref().unwrap().0;
+        for (seat, connection_id) in connections.iter().enumerate() {
+            match &transport.messages_for(connection_id)[..] {
+                [Message::LobbyUpdate(lobby), Message::GameUpdate(update), Message::ScoreUpdate(scores)] =>
+                {
+                    assert!(lobby.lobby.players.iter().all(|p| !p.ready));
+                    assert_eq!(update.game.seat, seat);
+                    assert_eq!(update.game.hand, game.hands[seat]);
+                    assert_eq!(update.game.seats.len(), 4);
+                    assert_eq!(scores.totals, vec![0; 4]);
+                }
+                other => panic!("unexpected messages {:?}", other),
+            }
+        }
+    }
+}
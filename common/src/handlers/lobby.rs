@@ -0,0 +1,637 @@
+use messages::Message;
+
+use crate::handlers;
+use crate::lobby::{Lobby, LobbyCodes, LobbyService, Player};
+use crate::router::Context;
+
+pub async fn create(
+    ctx: &Context<'_>,
+    message: messages::LobbyActionCreate,
+) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
+    let lobby = LobbyService::create(
+        ctx.repo,
+        &ctx.now,
+        &message.name,
+        ctx.connection_id,
+        &LobbyCodes::from_env(),
+    )
+    .await?;
+    ctx.repo
+        .set_connection_lobby(ctx.connection_id, &lobby.id)
+        .await?;
+    for player in lobby.players.iter() {
+        ctx.transport
+            .post_to_connection(
+                &player.connection_id,
+                Message::LobbyActionCreateResponse(messages::LobbyActionCreateResponse {
+                    lobby: (&lobby).into(),
+                    you: player.into(),
+                    session_token: player.session_token.clone(),
+                }),
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+pub async fn join(
+    ctx: &Context<'_>,
+    message: messages::LobbyActionJoin,
+) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
+    let lobby = LobbyService::join(
+        ctx.repo,
+        &ctx.now,
+        &message.lobby_code,
+        &message.name,
+        ctx.connection_id,
+    )
+    .await?;
+    ctx.repo
+        .set_connection_lobby(ctx.connection_id, &lobby.id)
+        .await?;
+    for player in lobby.players.iter().filter(|p| !p.away) {
+        let (history, more_history) = lobby.chat_page(&ctx.now, player, None);
+        ctx.transport
+            .post_to_connection(
+                &player.connection_id,
+                Message::LobbyActionJoinResponse(messages::LobbyActionJoinResponse {
+                    lobby: (&lobby).into(),
+                    you: player.into(),
+                    session_token: player.session_token.clone(),
+                    history: history.iter().map(|m| m.into()).collect(),
+                    more_history,
+                }),
+            )
+            .await?;
+    }
+    // Announce the name the player ended up with, which has a number added if it was taken.
+    match lobby.player(ctx.connection_id) {
+        Some(player) => {
+            let body = format!("{} joined", player.name);
+            handlers::chat::announce(ctx, &lobby, body).await
+        }
+        None => Ok(()),
+    }
+}
+
+pub async fn resume(
+    ctx: &Context<'_>,
+    message: messages::Resume,
+) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
+    let lobby = LobbyService::resume(
+        ctx.repo,
+        &ctx.now,
+        &message.lobby_code,
+        &message.session_token,
+        ctx.connection_id,
+    )
+    .await?;
+    ctx.repo
+        .set_connection_lobby(ctx.connection_id, &lobby.id)
+        .await?;
+    send_lobby(ctx, &lobby).await?;
+    // Everyone else still connected sees the player is back.
+    for player in lobby.players.iter().filter(|p| !p.away) {
+        if &player.connection_id != ctx.connection_id {
+            ctx.transport
+                .post_to_connection(
+                    &player.connection_id,
+                    Message::LobbyUpdate(messages::LobbyUpdate {
+                        lobby: (&lobby).into(),
+                    }),
+                )
+                .await?;
+        }
+    }
+    handlers::game::send_state(ctx, &lobby, ctx.connection_id).await
+}
+
+/// Stop a player from chatting, or let them again, and show everyone who is muted.
+pub async fn mute(
+    ctx: &Context<'_>,
+    message: messages::LobbyActionMute,
+) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
+    let lobby = LobbyService::mute_player(
+        ctx.repo,
+        &ctx.now,
+        &message.lobby_code,
+        ctx.connection_id,
+        &message.name,
+        message.muted,
+    )
+    .await?;
+    broadcast_lobby(ctx, &lobby).await
+}
+
+/// Remove the player called `name` from the lobby, telling them and everyone left.
+pub async fn kick(
+    ctx: &Context<'_>,
+    lobby_code: &String,
+    name: &String,
+) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
+    let (lobby, removed) =
+        LobbyService::kick_player(ctx.repo, &ctx.now, lobby_code, ctx.connection_id, name).await?;
+    broadcast_lobby(ctx, &lobby).await?;
+    send_removed(ctx, &lobby, &removed).await?;
+    let body = format!("{} was removed by the host", removed.name);
+    handlers::chat::announce(ctx, &lobby, body).await
+}
+
+pub async fn leave(
+    ctx: &Context<'_>,
+    message: messages::LobbyActionLeave,
+) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
+    let (lobby, removed) =
+        LobbyService::leave(ctx.repo, &ctx.now, &message.lobby_code, ctx.connection_id).await?;
+    broadcast_lobby(ctx, &lobby).await?;
+    send_removed(ctx, &lobby, &removed).await?;
+    handlers::chat::announce(ctx, &lobby, format!("{} left", removed.name)).await?;
+    announce_host(ctx, &lobby, &removed.connection_id).await
+}
+
+pub async fn transfer_host(
+    ctx: &Context<'_>,
+    message: messages::LobbyActionTransferHost,
+) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
+    let lobby = LobbyService::transfer_host(
+        ctx.repo,
+        &ctx.now,
+        &message.lobby_code,
+        ctx.connection_id,
+        &message.name,
+    )
+    .await?;
+    broadcast_lobby(ctx, &lobby).await?;
+    announce_host(ctx, &lobby, ctx.connection_id).await
+}
+
+pub async fn lock(
+    ctx: &Context<'_>,
+    message: messages::LobbyActionLock,
+) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
+    let lobby = LobbyService::set_locked(
+        ctx.repo,
+        &ctx.now,
+        &message.lobby_code,
+        ctx.connection_id,
+        message.locked,
+    )
+    .await?;
+    broadcast_lobby(ctx, &lobby).await?;
+    let body = if lobby.locked {
+        "The host locked the lobby"
+    } else {
+        "The host unlocked the lobby"
+    };
+    handlers::chat::announce(ctx, &lobby, body.to_string()).await
+}
+
+pub async fn take_seat(
+    ctx: &Context<'_>,
+    message: messages::LobbyActionTakeSeat,
+) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
+    let lobby = LobbyService::take_seat(
+        ctx.repo,
+        &ctx.now,
+        &message.lobby_code,
+        ctx.connection_id,
+        message.seat,
+    )
+    .await?;
+    broadcast_lobby(ctx, &lobby).await
+}
+
+pub async fn swap_seats(
+    ctx: &Context<'_>,
+    message: messages::LobbyActionSwapSeats,
+) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
+    let lobby = LobbyService::swap_seats(
+        ctx.repo,
+        &ctx.now,
+        &message.lobby_code,
+        ctx.connection_id,
+        message.first,
+        message.second,
+    )
+    .await?;
+    broadcast_lobby(ctx, &lobby).await
+}
+
+/// Mark the player on this connection as ready for the next game or not, dealing it if that was
+/// the last player the lobby was waiting for.
+pub async fn ready(
+    ctx: &Context<'_>,
+    lobby_code: &String,
+    ready: bool,
+) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
+    let (lobby, started) =
+        LobbyService::set_ready(ctx.repo, &ctx.now, lobby_code, ctx.connection_id, ready).await?;
+    if let Some(player) = lobby.player(ctx.connection_id) {
+        let body = if ready {
+            format!("{} is ready", player.name)
+        } else {
+            format!("{} is not ready", player.name)
+        };
+        handlers::chat::announce(ctx, &lobby, body).await?;
+    }
+    if started {
+        handlers::game::started(ctx, &lobby).await
+    } else {
+        broadcast_lobby(ctx, &lobby).await
+    }
+}
+
+pub async fn auto_start(
+    ctx: &Context<'_>,
+    message: messages::LobbyActionAutoStart,
+) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
+    let (lobby, started) = LobbyService::set_auto_start(
+        ctx.repo,
+        &ctx.now,
+        &message.lobby_code,
+        ctx.connection_id,
+        message.auto_start,
+    )
+    .await?;
+    if started {
+        handlers::game::started(ctx, &lobby).await
+    } else {
+        broadcast_lobby(ctx, &lobby).await
+    }
+}
+
+/// Mark the player on this connection as away in the lobby it was in, now that it has closed.
+pub async fn disconnect(
+    ctx: &Context<'_>
+) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
+    let lobby_id = match ctx.repo.connection_lobby(ctx.connection_id).await? {
+        Some(lobby_id) => lobby_id,
+        None => return Ok(()),
+    };
+    let lobby =
+        match LobbyService::disconnect(ctx.repo, &ctx.now, &lobby_id, ctx.connection_id).await? {
+            Some(lobby) => lobby,
+            None => return Ok(()),
+        };
+    broadcast_lobby(ctx, &lobby).await?;
+    announce_host(ctx, &lobby, ctx.connection_id).await
+}
+
+/// Tell everyone who the new host is, if it is no longer the player with `previous_host`.
+async fn announce_host(
+    ctx: &Context<'_>,
+    lobby: &Lobby,
+    previous_host: &String,
+) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
+    if lobby.is_host(previous_host) {
+        return Ok(());
+    }
+    match lobby.player(&lobby.host) {
+        Some(host) => {
+            let body = format!("{} is now the host", host.name);
+            handlers::chat::announce(ctx, lobby, body).await
+        }
+        None => Ok(()),
+    }
+}
+
+/// Let a player who is no longer in the lobby know, by sending them a lobby without them.
+async fn send_removed(
+    ctx: &Context<'_>,
+    lobby: &Lobby,
+    removed: &Player,
+) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
+    ctx.transport
+        .post_to_connection(
+            &removed.connection_id,
+            Message::LobbyUpdate(messages::LobbyUpdate {
+                lobby: lobby.into(),
+            }),
+        )
+        .await
+}
+
+/// Send the lobby to every player in it who is still connected.
+pub async fn broadcast_lobby(
+    ctx: &Context<'_>,
+    lobby: &Lobby,
+) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
+    for player in lobby.players.iter().filter(|p| !p.away) {
+        ctx.transport
+            .post_to_connection(
+                &player.connection_id,
+                Message::LobbyUpdate(messages::LobbyUpdate {
+                    lobby: lobby.into(),
+                }),
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+/// Send the lobby to the player on this connection, who they are in it, and its recent chat.
+async fn send_lobby(
+    ctx: &Context<'_>,
+    lobby: &Lobby,
+) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
+    let player = match lobby.player(ctx.connection_id) {
+        Some(player) => player,
+        None => return Ok(()),
+    };
+    let (history, more_history) = lobby.chat_page(&ctx.now, player, None);
+    ctx.transport
+        .post_to_connection(
+            ctx.connection_id,
+            Message::ResumeResponse(messages::ResumeResponse {
+                lobby: lobby.into(),
+                you: player.into(),
+                history: history.iter().map(|m| m.into()).collect(),
+                more_history,
+            }),
+        )
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+    use crate::lobby::{InMemoryLobbyRepo, LobbyCodes, LobbyRepo};
+    use crate::moderation::WordFilter;
+    use crate::transport::RecordingTransport;
+
+    #[tokio::test]
+    async fn create_then_join_notifies_every_player() {
+        let repo = InMemoryLobbyRepo::new();
+        let transport = RecordingTransport::new();
+        let host = "host-conn".to_string();
+        let amigo = "amigo-conn".to_string();
+
+        let host_ctx = Context {
+            repo: &repo,
+            transport: &transport,
+            connection_id: &host,
+            now: Utc::now(),
+        };
+        create(
+            &host_ctx,
+            messages::LobbyActionCreate {
+                name: "Host".to_string(),
+            },
+        )
+        .await
+        .expect("failed to create lobby");
+        let lobby_id = match &transport.messages_for(&host)[..] {
+            [Message::LobbyActionCreateResponse(response)] => response.lobby.id.clone(),
+            other => panic!("unexpected messages {:?}", other),
+        };
+
+        let amigo_ctx = Context {
+            connection_id: &amigo,
+            ..host_ctx
+        };
+        join(
+            &amigo_ctx,
+            messages::LobbyActionJoin {
+                name: "Amigo".to_string(),
+                lobby_code: lobby_id.clone(),
+            },
+        )
+        .await
+        .expect("failed to join lobby");
+
+        let stored = LobbyService::get(&repo, &lobby_id).await.unwrap();
+        let lobby = messages::Lobby::from(&stored);
+        let expected_for = |player: &crate::lobby::Player| {
+            Message::LobbyActionJoinResponse(messages::LobbyActionJoinResponse {
+                lobby: lobby.clone(),
+                you: player.into(),
+                session_token: player.session_token.clone(),
+                history: vec![],
+                more_history: false,
+            })
+        };
+        let names: Vec<&str> = lobby.players.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["Host", "Amigo"]);
+        assert_ne!(
+            stored.players[0].session_token,
+            stored.players[1].session_token
+        );
+        let host_messages = transport.messages_for(&host);
+        assert_eq!(
+            host_messages[host_messages.len() - 2],
+            expected_for(&stored.players[0])
+        );
+        match &transport.messages_for(&amigo)[..] {
+            [response, Message::SystemMessage(joined)] => {
+                assert_eq!(response, &expected_for(&stored.players[1]));
+                assert_eq!(joined.body, "Amigo joined");
+            }
+            other => panic!("unexpected messages {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn resume_moves_player_to_the_new_connection() {
+        let repo = InMemoryLobbyRepo::new();
+        let transport = RecordingTransport::new();
+        let old = "old-conn".to_string();
+        let new = "new-conn".to_string();
+        let lobby = LobbyService::create(
+            &repo,
+            &Utc::now(),
+            &"Host".to_string(),
+            &old,
+            &LobbyCodes::default(),
+        )
+        .await
+        .expect("failed to create lobby");
+        LobbyService::post_message(
+            &repo,
+            &Utc::now(),
+            &lobby.id,
+            &old,
+            "brb".to_string(),
+            None,
+            &WordFilter::default(),
+        )
+        .await
+        .expect("failed to post message");
+
+        let ctx = Context {
+            repo: &repo,
+            transport: &transport,
+            connection_id: &new,
+            now: Utc::now(),
+        };
+        resume(
+            &ctx,
+            messages::Resume {
+                lobby_code: lobby.id.clone(),
+                session_token: lobby.players[0].session_token.clone(),
+            },
+        )
+        .await
+        .expect("failed to resume");
+
+        match &transport.messages_for(&new)[..] {
+            [Message::ResumeResponse(response)] => {
+                assert_eq!(response.you.name, "Host");
+                assert_eq!(response.you.connection_id, new);
+                let bodies: Vec<&str> = response.history.iter().map(|m| m.body.as_str()).collect();
+                assert_eq!(bodies, vec!["brb"]);
+            }
+            other => panic!("unexpected messages {:?}", other),
+        }
+
+        let wrong_token = resume(
+            &ctx,
+            messages::Resume {
+                lobby_code: lobby.id.clone(),
+                session_token: "guessed".to_string(),
+            },
+        )
+        .await;
+        assert!(wrong_token.is_err());
+    }
+
+    #[tokio::test]
+    async fn disconnecting_host_hands_the_lobby_on() {
+        let repo = InMemoryLobbyRepo::new();
+        let transport = RecordingTransport::new();
+        let host = "host-conn".to_string();
+        let amigo = "amigo-conn".to_string();
+        let host_ctx = Context {
+            repo: &repo,
+            transport: &transport,
+            connection_id: &host,
+            now: Utc::now(),
+        };
+        create(
+            &host_ctx,
+            messages::LobbyActionCreate {
+                name: "Host".to_string(),
+            },
+        )
+        .await
+        .expect("failed to create lobby");
+        let lobby_id = repo.connection_lobby(&host).await.unwrap().unwrap();
+        let amigo_ctx = Context {
+            connection_id: &amigo,
+            now: Utc::now(),
+            ..host_ctx
+        };
+        join(
+            &amigo_ctx,
+            messages::LobbyActionJoin {
+                name: "Amigo".to_string(),
+                lobby_code: lobby_id.clone(),
+            },
+        )
+        .await
+        .expect("failed to join lobby");
+        let sent_to_host = transport.messages_for(&host).len();
+
+        let closed_ctx = Context {
+            repo: &repo,
+            transport: &transport,
+            connection_id: &host,
+            now: Utc::now(),
+        };
+        disconnect(&closed_ctx).await.expect("failed to disconnect");
+
+        // Nothing more is sent to the closed connection.
+        assert_eq!(transport.messages_for(&host).len(), sent_to_host);
+        match &transport.messages_for(&amigo)[2..] {
+            [Message::LobbyUpdate(update), Message::SystemMessage(announced)] => {
+                assert_eq!(update.lobby.host, amigo);
+                assert!(update.lobby.players[0].away);
+                assert_eq!(announced.body, "Amigo is now the host");
+            }
+            other => panic!("unexpected messages {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn players_who_are_away_are_left_out() {
+        let repo = InMemoryLobbyRepo::new();
+        let transport = RecordingTransport::new();
+        let host = "host-conn".to_string();
+        let amigo = "amigo-conn".to_string();
+        let newcomer = "newcomer-conn".to_string();
+        let host_ctx = Context {
+            repo: &repo,
+            transport: &transport,
+            connection_id: &host,
+            now: Utc::now(),
+        };
+        create(
+            &host_ctx,
+            messages::LobbyActionCreate {
+                name: "Host".to_string(),
+            },
+        )
+        .await
+        .expect("failed to create lobby");
+        let lobby_id = repo.connection_lobby(&host).await.unwrap().unwrap();
+        let amigo_ctx = Context {
+            connection_id: &amigo,
+            ..host_ctx
+        };
+        join(
+            &amigo_ctx,
+            messages::LobbyActionJoin {
+                name: "Amigo".to_string(),
+                lobby_code: lobby_id.clone(),
+            },
+        )
+        .await
+        .expect("failed to join lobby");
+        disconnect(&amigo_ctx).await.expect("failed to disconnect");
+        transport.close(&amigo);
+
+        join(
+            &Context {
+                connection_id: &newcomer,
+                ..host_ctx
+            },
+            messages::LobbyActionJoin {
+                name: "Newcomer".to_string(),
+                lobby_code: lobby_id.clone(),
+            },
+        )
+        .await
+        .expect("failed to join lobby with a player away");
+        handlers::chat::send(
+            &host_ctx,
+            messages::LobbyMessage {
+                lobby_code: lobby_id.clone(),
+                body: "welcome".to_string(),
+                client_id: None,
+                to: None,
+            },
+        )
+        .await
+        .expect("failed to chat with a player away");
+        handlers::chat::react(
+            &host_ctx,
+            messages::LobbyReaction {
+                lobby_code: lobby_id.clone(),
+                reaction: messages::Reaction::Gg,
+                message_id: None,
+            },
+        )
+        .await
+        .expect("failed to react with a player away");
+
+        assert_eq!(transport.messages_for(&amigo).len(), 2);
+        match &transport.messages_for(&newcomer)[2..] {
+            [Message::LobbyMessageResponse(chat), Message::LobbyReactionResponse(reaction)] => {
+                assert_eq!(chat.body, "welcome");
+                assert_eq!(reaction.name, "Host");
+            }
+            other => panic!("unexpected messages {:?}", other),
+        }
+    }
+}
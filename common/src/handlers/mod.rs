@@ -0,0 +1,4 @@
+pub mod chat;
+pub mod commands;
+pub mod game;
+pub mod lobby;
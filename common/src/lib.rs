@@ -1,2 +1,7 @@
+pub mod event;
+pub mod handlers;
 pub mod lobby;
+pub mod moderation;
+pub mod router;
+pub mod transport;
 pub mod websocket_client;
@@ -1,25 +1,175 @@
-use std::collections::HashMap;
-use std::env;
 use std::error::Error;
 use std::fmt;
 
 use chrono::{DateTime, Utc};
-use dynomite::{
-    attr_map,
-    dynamodb::{DynamoDb, DynamoDbClient, GetItemInput, PutItemInput},
-    AttributeValue, Attributes, FromAttributes, Item,
-};
+use dynomite::{Attribute, AttributeError, AttributeValue, Attributes, Item};
+use game::{Game, Played, Seat};
+use messages::{ErrorKind, Recipient};
 use nanoid::nanoid;
+
+use crate::moderation::{WordFilter, CHAT_RATE_LIMIT, CHAT_RATE_WINDOW_SECONDS};
+use rand::seq::SliceRandom;
 use serde::{Deserialize, Serialize};
 
+mod codes;
+mod names;
+mod repo;
+pub use codes::{LobbyCodes, LOBBY_CODE_ALPHABET};
+pub use names::NameError;
+pub use repo::{DynamoDbLobbyRepo, InMemoryLobbyRepo, LobbyRepo};
+
 #[derive(Attributes, Debug, Serialize, Deserialize, Clone)]
 pub struct Player {
+    /// Tells players apart for as long as they are in the lobby, unlike their name which someone
+    /// else may take once they leave, or their connection which changes when they resume.
+    #[dynomite(default)]
+    #[serde(default)]
+    pub id: String,
     pub name: String,
     pub connection_id: String,
+    /// A secret only this player is told, which lets them take their place back from a new
+    /// connection.
+    #[dynomite(default)]
+    #[serde(default)]
+    pub session_token: String,
+    /// Whether the host has stopped this player from chatting.
+    #[dynomite(default)]
+    #[serde(default)]
+    pub muted: bool,
+    /// When this player sent their latest chat messages, to limit how quickly they can send more.
+    #[dynomite(default)]
+    #[serde(default)]
+    pub recent_messages: Vec<DateTime<Utc>>,
+    /// Whether this player's connection has closed and they have not resumed since.
+    #[dynomite(default)]
+    #[serde(default)]
+    pub away: bool,
+    /// Whether this player is ready for the next game to be dealt.
+    #[dynomite(default)]
+    #[serde(default)]
+    pub ready: bool,
+}
+
+impl Player {
+    fn new(
+        name: &String,
+        connection_id: &String,
+    ) -> Self {
+        Player {
+            id: nanoid!(12),
+            name: name.to_string(),
+            connection_id: connection_id.to_string(),
+            session_token: nanoid!(32),
+            muted: false,
+            recent_messages: Vec::new(),
+            away: false,
+            ready: false,
+        }
+    }
+
+    /// Refuse anything a player the host has muted sends to the chat.
+    fn check_not_muted(&self) -> Result<(), LobbyServiceError> {
+        if self.muted {
+            return Err(LobbyServiceError::new(
+                ErrorKind::Muted,
+                "The host has muted you",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Count something sent to the chat at `now` against `CHAT_RATE_LIMIT`, refusing it if this
+    /// player is sending too quickly.
+    fn count_message(
+        &mut self,
+        now: &DateTime<Utc>,
+    ) -> Result<(), LobbyServiceError> {
+        let window_start = *now - chrono::Duration::seconds(CHAT_RATE_WINDOW_SECONDS);
+        self.recent_messages
+            .retain(|&sent_at| sent_at > window_start);
+        if self.recent_messages.len() >= CHAT_RATE_LIMIT {
+            return Err(LobbyServiceError::new(
+                ErrorKind::RateLimited,
+                "You are sending messages too quickly, wait a moment",
+            ));
+        }
+        self.recent_messages.push(*now);
+        Ok(())
+    }
 }
 
 pub type LobbyId = String;
 
+/// How many codes `LobbyService::create` tries before giving up, should they all be taken.
+const LOBBY_CODE_ATTEMPTS: usize = 5;
+
+/// The most chat messages a lobby keeps, dropping the oldest beyond that.
+pub const CHAT_HISTORY_LIMIT: usize = 200;
+/// The most bytes of chat message bodies a lobby keeps, dropping the oldest beyond that, so the
+/// lobby stays well inside the 400KB DynamoDB allows for an item.
+pub const CHAT_HISTORY_BYTES: usize = 64 * 1024;
+/// How long a chat message is kept for.
+pub const CHAT_TTL_HOURS: i64 = 24;
+/// How many chat messages are sent at a time, on joining or when asking for older ones.
+pub const CHAT_PAGE_SIZE: usize = 50;
+
+#[derive(Attributes, Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ChatMessage {
+    pub id: String,
+    pub sent_at: DateTime<Utc>,
+    pub name: String,
+    pub body: String,
+    /// The name of the only player the message was for.
+    #[dynomite(default)]
+    #[serde(default)]
+    pub to: Option<String>,
+    /// Whether the message was only for the sender's partner.
+    #[dynomite(default)]
+    #[serde(default)]
+    pub team: bool,
+    /// The ids of the players who can see the message, or empty if everyone can.
+    #[dynomite(default)]
+    #[serde(default)]
+    pub visible_to: Vec<String>,
+    /// Whether the server said this, like who joined or a command's answer, rather than `name`.
+    #[dynomite(default)]
+    #[serde(default)]
+    pub system: bool,
+}
+
+impl ChatMessage {
+    /// Whether `player` can see the message, which a player without an id only can if it was
+    /// sent to everyone.
+    pub fn is_visible_to(
+        &self,
+        player: &Player,
+    ) -> bool {
+        self.visible_to.is_empty()
+            || (!player.id.is_empty() && self.visible_to.contains(&player.id))
+    }
+}
+
+/// A `Game` kept as a JSON string attribute, since nothing needs to query inside it.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(transparent)]
+pub struct GameState(pub Game);
+
+impl Attribute for GameState {
+    fn into_attr(self) -> AttributeValue {
+        AttributeValue {
+            s: Some(serde_json::to_string(&self.0).unwrap_or_default()),
+            ..AttributeValue::default()
+        }
+    }
+
+    fn from_attr(value: AttributeValue) -> Result<Self, AttributeError> {
+        let json = value.s.ok_or(AttributeError::InvalidType)?;
+        serde_json::from_str(&json)
+            .map(GameState)
+            .map_err(|_| AttributeError::InvalidFormat)
+    }
+}
+
 #[derive(Item, Debug, Serialize, Deserialize, Clone)]
 pub struct Lobby {
     #[dynomite(partition_key)]
@@ -27,19 +177,328 @@ pub struct Lobby {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub players: Vec<Player>,
+    /// The connection id of the player who runs the lobby.
+    #[dynomite(default)]
+    #[serde(default)]
+    pub host: String,
+    /// Whether the host has stopped anyone else from joining.
+    #[dynomite(default)]
+    #[serde(default)]
+    pub locked: bool,
+    /// Whether a game is dealt as soon as everyone at the table is ready, without a start action.
+    #[dynomite(default)]
+    #[serde(default)]
+    pub auto_start: bool,
+    /// The connection id of the player in each seat, clockwise from North, or empty while
+    /// nobody sits there.
+    #[dynomite(default)]
+    #[serde(default)]
+    pub seats: Vec<String>,
+    /// The game being played by the seated players, once it has been started.
+    #[dynomite(default)]
+    pub game: Option<GameState>,
+    /// Chat messages sent in the lobby, oldest first, within `CHAT_TTL_HOURS` of the last one.
+    #[dynomite(default)]
+    #[serde(default)]
+    pub chat: Vec<ChatMessage>,
 }
 
+impl Lobby {
+    /// The seat at the table of the player with `connection_id`.
+    pub fn seat_of(
+        &self,
+        connection_id: &String,
+    ) -> Option<Seat> {
+        self.seats
+            .iter()
+            .position(|c| !c.is_empty() && c == connection_id)
+    }
+
+    /// The player sitting in `seat`.
+    pub fn seated(
+        &self,
+        seat: Seat,
+    ) -> Option<&Player> {
+        self.seats.get(seat).and_then(|c| self.player(c))
+    }
+
+    /// The first seat nobody is sitting in.
+    fn open_seat(&self) -> Option<Seat> {
+        (0..game::PLAYERS).find(|&seat| self.seated(seat).is_none())
+    }
+
+    /// Put the player with `connection_id` in `seat`, leaving the seat they were in open.
+    fn sit(
+        &mut self,
+        seat: Seat,
+        connection_id: &String,
+    ) {
+        self.seats.resize(game::PLAYERS, String::new());
+        for taken in self.seats.iter_mut().filter(|c| c == &connection_id) {
+            taken.clear();
+        }
+        self.seats[seat] = connection_id.to_string();
+    }
+
+    pub fn player(
+        &self,
+        connection_id: &String,
+    ) -> Option<&Player> {
+        self.players
+            .iter()
+            .find(|p| &p.connection_id == connection_id)
+    }
+
+    /// Whether the player with `connection_id` runs the lobby.
+    pub fn is_host(
+        &self,
+        connection_id: &String,
+    ) -> bool {
+        &self.host == connection_id
+    }
+
+    /// Who should take over as host from the player with `connection_id`, preferring whoever
+    /// joined first of the players who are still connected.
+    fn next_host(
+        &self,
+        connection_id: &String,
+    ) -> Option<&Player> {
+        let others = || {
+            self.players
+                .iter()
+                .filter(move |p| &p.connection_id != connection_id)
+        };
+        others().find(|p| !p.away).or_else(|| others().next())
+    }
+
+    /// Take the player at `index` out of the lobby, handing the host on if it was them.
+    fn remove_player(
+        &mut self,
+        index: usize,
+    ) -> Result<Player, LobbyServiceError> {
+        let connection_id = self.players[index].connection_id.clone();
+        if self.game_in_progress() && self.seat_of(&connection_id).is_some() {
+            return Err(LobbyServiceError::new(
+                ErrorKind::InvalidAction,
+                "Players at the table can't leave during a game",
+            ));
+        }
+        if self.is_host(&connection_id) {
+            self.host = self
+                .next_host(&connection_id)
+                .map(|p| p.connection_id.clone())
+                .unwrap_or_default();
+        }
+        for seat in self.seats.iter_mut().filter(|c| c == &&connection_id) {
+            seat.clear();
+        }
+        Ok(self.players.remove(index))
+    }
+
+    fn game_in_progress(&self) -> bool {
+        match &self.game {
+            Some(GameState(game)) => !game.is_finished(),
+            None => false,
+        }
+    }
+
+    /// The players at the table who are not ready for a game yet.
+    pub fn not_ready(&self) -> Vec<&Player> {
+        (0..game::PLAYERS)
+            .filter_map(|seat| self.seated(seat))
+            .filter(|p| !p.ready)
+            .collect()
+    }
+
+    /// Whether a full table is ready and waiting for a game.
+    fn can_start(&self) -> bool {
+        self.open_seat().is_none() && self.not_ready().is_empty() && !self.game_in_progress()
+    }
+
+    /// Deal a new game, after which everyone has to be ready again for the one after it.
+    fn deal(&mut self) {
+        self.game = Some(GameState(Game::new(shuffled_deck())));
+        for player in self.players.iter_mut() {
+            player.ready = false;
+        }
+    }
+
+    /// The ids of the players who can see a message from the player with `connection_id` to `to`,
+    /// or none for everyone.
+    pub fn recipients(
+        &self,
+        connection_id: &String,
+        to: Option<&Recipient>,
+    ) -> Result<Vec<String>, LobbyServiceError> {
+        let sender = self.player(connection_id).ok_or_else(|| {
+            LobbyServiceError::new(ErrorKind::PlayerNotFound, "You are not in this lobby")
+        })?;
+        let recipient = match to {
+            None => return Ok(Vec::new()),
+            Some(Recipient::Player { name }) => self
+                .players
+                .iter()
+                .find(|p| &p.name == name)
+                .ok_or_else(|| {
+                    LobbyServiceError::new(
+                        ErrorKind::PlayerNotFound,
+                        "Nobody in the lobby has that name",
+                    )
+                })?,
+            Some(Recipient::Team) => {
+                let seat = self.seat_of(connection_id).ok_or_else(|| {
+                    LobbyServiceError::new(ErrorKind::InvalidAction, "You are not at the table")
+                })?;
+                self.seated((seat + 2) % game::PLAYERS).ok_or_else(|| {
+                    LobbyServiceError::new(
+                        ErrorKind::InvalidAction,
+                        "Nobody is sitting across from you yet",
+                    )
+                })?
+            }
+        };
+        if recipient.connection_id == sender.connection_id {
+            return Err(LobbyServiceError::new(
+                ErrorKind::InvalidAction,
+                "You can't send a message only to yourself",
+            ));
+        }
+        Ok(vec![sender.id.clone(), recipient.id.clone()])
+    }
+
+    /// Add `message` to the chat, dropping expired messages and the oldest beyond the limits.
+    fn keep_message(
+        &mut self,
+        now: &DateTime<Utc>,
+        message: ChatMessage,
+    ) {
+        let expired = *now - chrono::Duration::hours(CHAT_TTL_HOURS);
+        self.chat.retain(|m| m.sent_at > expired);
+        self.chat.push(message);
+        let excess = self.chat.len().saturating_sub(CHAT_HISTORY_LIMIT);
+        self.chat.drain(..excess);
+        let mut bytes: usize = self.chat.iter().map(|m| m.body.len()).sum();
+        while bytes > CHAT_HISTORY_BYTES {
+            bytes -= self.chat.remove(0).body.len();
+        }
+    }
+
+    /// Up to `CHAT_PAGE_SIZE` chat messages `viewer` can see, sent before the message with id
+    /// `before` or the latest ones without it, along with whether there are older messages still.
+    pub fn chat_page(
+        &self,
+        now: &DateTime<Utc>,
+        viewer: &Player,
+        before: Option<&String>,
+    ) -> (Vec<ChatMessage>, bool) {
+        let expired = *now - chrono::Duration::hours(CHAT_TTL_HOURS);
+        let live: Vec<&ChatMessage> = self
+            .chat
+            .iter()
+            .filter(|m| m.sent_at > expired && m.is_visible_to(viewer))
+            .collect();
+        // A message that is no longer kept was older than everything that is.
+        let end = match before {
+            Some(id) => live.iter().position(|m| &m.id == id).unwrap_or(0),
+            None => live.len(),
+        };
+        let start = end.saturating_sub(CHAT_PAGE_SIZE);
+        let page = live[start..end].iter().map(|&m| m.clone()).collect();
+        (page, start > 0)
+    }
+}
+
+impl From<&ChatMessage> for messages::LobbyMessageResponse {
+    fn from(message: &ChatMessage) -> Self {
+        messages::LobbyMessageResponse {
+            id: message.id.clone(),
+            sent_at: message.sent_at,
+            name: message.name.clone(),
+            body: message.body.clone(),
+            client_id: None,
+            system: message.system,
+            to: if message.team {
+                Some(Recipient::Team)
+            } else {
+                message
+                    .to
+                    .as_ref()
+                    .map(|name| Recipient::Player { name: name.clone() })
+            },
+        }
+    }
+}
+
+impl From<&ChatMessage> for messages::SystemMessage {
+    fn from(message: &ChatMessage) -> Self {
+        messages::SystemMessage {
+            id: message.id.clone(),
+            sent_at: message.sent_at,
+            body: message.body.clone(),
+        }
+    }
+}
+
+impl From<&Player> for messages::Player {
+    fn from(player: &Player) -> Self {
+        messages::Player {
+            name: player.name.clone(),
+            connection_id: player.connection_id.clone(),
+            muted: player.muted,
+            away: player.away,
+            ready: player.ready,
+        }
+    }
+}
+
+impl From<&Lobby> for messages::Lobby {
+    fn from(lobby: &Lobby) -> Self {
+        messages::Lobby {
+            id: lobby.id.clone(),
+            players: lobby.players.iter().map(|p| p.into()).collect(),
+            host: lobby.host.clone(),
+            locked: lobby.locked,
+            auto_start: lobby.auto_start,
+            seats: (0..game::PLAYERS)
+                .map(|seat| lobby.seated(seat).map(|p| p.connection_id.clone()))
+                .collect(),
+        }
+    }
+}
+
+/// An error caused by what a player asked for, which is reported back to them.
 #[derive(Debug)]
-struct LobbyServiceError {
+pub struct LobbyServiceError {
+    kind: ErrorKind,
     details: String,
 }
 
 impl LobbyServiceError {
-    fn new(msg: &str) -> LobbyServiceError {
+    pub fn new(
+        kind: ErrorKind,
+        msg: &str,
+    ) -> LobbyServiceError {
         LobbyServiceError {
+            kind,
             details: msg.to_string(),
         }
     }
+
+    /// The lobby changed between being read and written back, usually by another player.
+    pub fn conflict() -> LobbyServiceError {
+        LobbyServiceError::new(
+            ErrorKind::TryAgain,
+            "The lobby changed while this was being done, try again",
+        )
+    }
+
+    pub fn to_response(&self) -> messages::ErrorResponse {
+        messages::ErrorResponse {
+            kind: self.kind.clone(),
+            message: self.details.clone(),
+            path: None,
+        }
+    }
 }
 
 impl fmt::Display for LobbyServiceError {
@@ -60,39 +519,51 @@ impl Error for LobbyServiceError {
 pub struct LobbyService;
 
 impl LobbyService {
+    /// Create a lobby hosted by the player with `connection_id`, under a code no other lobby has.
     pub async fn create(
-        ddb: &DynamoDbClient,
+        repo: &dyn LobbyRepo,
         now: &DateTime<Utc>,
         host_name: &String,
         connection_id: &String,
+        codes: &LobbyCodes,
     ) -> Result<Lobby, Box<dyn std::error::Error + Sync + Send + 'static>> {
         log::info!("Create: {}", host_name);
 
-        let mut players = Vec::new();
-        players.push(Player {
-            name: host_name.to_string(),
-            connection_id: connection_id.to_string(),
-        });
-        let lobby_code_alphabet: [char; 36] = [
-            '1', '2', '3', '4', '5', '6', '7', '8', '9', '0', 'a', 'b', 'c', 'd', 'e', 'f', 'g',
-            'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x',
-            'y', 'z',
-        ];
-        let lobby_code = nanoid!(4, &lobby_code_alphabet);
-        let lobby = Lobby {
-            id: lobby_code,
-            created_at: now.clone(),
-            updated_at: now.clone(),
+        let host_name = names::validate(host_name).map_err(LobbyServiceError::from)?;
+        let players = vec![Player::new(&host_name, connection_id)];
+        let mut lobby = Lobby {
+            id: codes.generate(),
+            created_at: *now,
+            updated_at: *now,
             players,
+            host: connection_id.to_string(),
+            locked: false,
+            auto_start: false,
+            seats: Vec::new(),
+            game: None,
+            chat: Vec::new(),
         };
 
-        log::info!("Lobby: {:?}", lobby);
-        LobbyRepo::put(ddb, &lobby).await?;
-        return Ok(lobby);
+        lobby.sit(0, connection_id);
+
+        // Codes are short enough that they do collide, so pick another until one is free.
+        for _ in 0..LOBBY_CODE_ATTEMPTS {
+            log::info!("Lobby: {:?}", lobby);
+            if repo.create(&lobby).await? {
+                return Ok(lobby);
+            }
+            log::warn!("Lobby code {} is taken", lobby.id);
+            lobby.id = codes.generate();
+        }
+        Err(LobbyServiceError::new(
+            ErrorKind::TryAgain,
+            "Every lobby code that was tried is taken, try again",
+        )
+        .into())
     }
 
     pub async fn join(
-        ddb: &DynamoDbClient,
+        repo: &dyn LobbyRepo,
         now: &DateTime<Utc>,
         lobby_id: &LobbyId,
         player_name: &String,
@@ -100,131 +571,1471 @@ impl LobbyService {
     ) -> Result<Lobby, Box<dyn std::error::Error + Sync + Send + 'static>> {
         log::info!("Join: {} {}", lobby_id, player_name);
 
-        let maybe_lobby = LobbyRepo::get(ddb, lobby_id).await?;
+        let maybe_lobby = repo.get(lobby_id).await?;
         log::info!("LobbyService::join get result: {:?}", &maybe_lobby);
 
-        let current_lobby = maybe_lobby
-            .ok_or(Box::new(LobbyServiceError::new("Could not get Lobby")))
-            .unwrap();
+        let current_lobby = maybe_lobby.ok_or_else(|| {
+            LobbyServiceError::new(ErrorKind::LobbyNotFound, "Could not get Lobby")
+        })?;
+        if current_lobby.locked {
+            return Err(LobbyServiceError::new(
+                ErrorKind::LobbyLocked,
+                "The host has locked this lobby",
+            )
+            .into());
+        }
+
+        let player_name = names::validate(player_name).map_err(LobbyServiceError::from)?;
+        let taken: Vec<&str> = current_lobby
+            .players
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect();
+        let player_name = names::disambiguate(player_name, &taken);
 
         let mut modified_lobby = current_lobby.clone();
 
-        modified_lobby.players.push(Player {
-            name: player_name.to_string(),
-            connection_id: connection_id.to_string(),
-        });
-        modified_lobby.updated_at = now.clone();
+        modified_lobby
+            .players
+            .push(Player::new(&player_name, connection_id));
+        if let Some(seat) = modified_lobby.open_seat() {
+            modified_lobby.sit(seat, connection_id);
+        }
+        modified_lobby.updated_at = *now;
 
-        let new_lobby = LobbyRepo::update(
-            ddb,
-            &current_lobby.id,
-            &current_lobby.updated_at,
-            &modified_lobby,
-        )
-        .await?;
+        let new_lobby = repo
+            .update(
+                &current_lobby.id,
+                &current_lobby.updated_at,
+                &modified_lobby,
+            )
+            .await?;
+
+        Ok(new_lobby)
+    }
+
+    /// Deal a new game for the players in the seats.
+    pub async fn start_game(
+        repo: &dyn LobbyRepo,
+        now: &DateTime<Utc>,
+        lobby_id: &LobbyId,
+        connection_id: &String,
+    ) -> Result<Lobby, Box<dyn std::error::Error + Sync + Send + 'static>> {
+        log::info!("Start game: {}", lobby_id);
+
+        let current_lobby = Self::get(repo, lobby_id).await?;
+        if current_lobby.seat_of(connection_id).is_none() {
+            return Err(LobbyServiceError::new(
+                ErrorKind::InvalidAction,
+                "Only players at the table can start a game",
+            )
+            .into());
+        }
+        if current_lobby.open_seat().is_some() {
+            return Err(LobbyServiceError::new(
+                ErrorKind::InvalidAction,
+                "A game needs a player in every seat",
+            )
+            .into());
+        }
+        if current_lobby.game_in_progress() {
+            return Err(LobbyServiceError::new(
+                ErrorKind::InvalidAction,
+                "A game is already in progress",
+            )
+            .into());
+        }
+        let not_ready = current_lobby.not_ready();
+        if !not_ready.is_empty() {
+            let names: Vec<&str> = not_ready.iter().map(|p| p.name.as_str()).collect();
+            return Err(LobbyServiceError::new(
+                ErrorKind::NotReady,
+                &format!("Waiting for {} to be ready", names.join(", ")),
+            )
+            .into());
+        }
+
+        let mut modified_lobby = current_lobby.clone();
+        modified_lobby.deal();
+        modified_lobby.updated_at = *now;
+
+        let new_lobby = repo
+            .update(
+                &current_lobby.id,
+                &current_lobby.updated_at,
+                &modified_lobby,
+            )
+            .await?;
+
+        Ok(new_lobby)
+    }
+
+    /// Sit the player with `connection_id` in `seat`, if nobody else is sitting there.
+    pub async fn take_seat(
+        repo: &dyn LobbyRepo,
+        now: &DateTime<Utc>,
+        lobby_id: &LobbyId,
+        connection_id: &String,
+        seat: Seat,
+    ) -> Result<Lobby, Box<dyn std::error::Error + Sync + Send + 'static>> {
+        log::info!("Take seat: {} {}", lobby_id, seat);
+
+        let current_lobby = Self::get(repo, lobby_id).await?;
+        if current_lobby.player(connection_id).is_none() {
+            return Err(LobbyServiceError::new(
+                ErrorKind::PlayerNotFound,
+                "You are not in this lobby",
+            )
+            .into());
+        }
+        if seat >= game::PLAYERS {
+            return Err(
+                LobbyServiceError::new(ErrorKind::InvalidAction, "There is no such seat").into(),
+            );
+        }
+        if current_lobby.game_in_progress() {
+            return Err(LobbyServiceError::new(
+                ErrorKind::InvalidAction,
+                "Nobody can change seats during a game",
+            )
+            .into());
+        }
+        if current_lobby.seated(seat).is_some() {
+            return Err(
+                LobbyServiceError::new(ErrorKind::InvalidAction, "That seat is taken").into(),
+            );
+        }
+
+        let mut modified_lobby = current_lobby.clone();
+        modified_lobby.sit(seat, connection_id);
+        modified_lobby.updated_at = *now;
+
+        let new_lobby = repo
+            .update(
+                &current_lobby.id,
+                &current_lobby.updated_at,
+                &modified_lobby,
+            )
+            .await?;
+
+        Ok(new_lobby)
+    }
+
+    /// Swap whoever is sitting in two seats, if `connection_id` is the host's.
+    pub async fn swap_seats(
+        repo: &dyn LobbyRepo,
+        now: &DateTime<Utc>,
+        lobby_id: &LobbyId,
+        connection_id: &String,
+        first: Seat,
+        second: Seat,
+    ) -> Result<Lobby, Box<dyn std::error::Error + Sync + Send + 'static>> {
+        log::info!("Swap seats: {} {} {}", lobby_id, first, second);
+
+        let current_lobby = Self::get(repo, lobby_id).await?;
+        if !current_lobby.is_host(connection_id) {
+            return Err(LobbyServiceError::new(
+                ErrorKind::InvalidAction,
+                "Only the host can move other players",
+            )
+            .into());
+        }
+        if first >= game::PLAYERS || second >= game::PLAYERS {
+            return Err(
+                LobbyServiceError::new(ErrorKind::InvalidAction, "There is no such seat").into(),
+            );
+        }
+        if current_lobby.game_in_progress() {
+            return Err(LobbyServiceError::new(
+                ErrorKind::InvalidAction,
+                "Nobody can change seats during a game",
+            )
+            .into());
+        }
+
+        let mut modified_lobby = current_lobby.clone();
+        modified_lobby.seats.resize(game::PLAYERS, String::new());
+        modified_lobby.seats.swap(first, second);
+        modified_lobby.updated_at = *now;
+
+        let new_lobby = repo
+            .update(
+                &current_lobby.id,
+                &current_lobby.updated_at,
+                &modified_lobby,
+            )
+            .await?;
+
+        Ok(new_lobby)
+    }
+
+    /// Mark the player with `connection_id` as ready for the next game, or not.
+    ///
+    /// Also returns whether that dealt a game, which it does once everyone at the table is ready
+    /// if the lobby starts games automatically.
+    pub async fn set_ready(
+        repo: &dyn LobbyRepo,
+        now: &DateTime<Utc>,
+        lobby_id: &LobbyId,
+        connection_id: &String,
+        ready: bool,
+    ) -> Result<(Lobby, bool), Box<dyn std::error::Error + Sync + Send + 'static>> {
+        log::info!("Ready: {} {}", lobby_id, ready);
+
+        let current_lobby = Self::get(repo, lobby_id).await?;
+        if current_lobby.seat_of(connection_id).is_none() {
+            return Err(LobbyServiceError::new(
+                ErrorKind::InvalidAction,
+                "Only players at the table need to be ready",
+            )
+            .into());
+        }
+        if current_lobby.game_in_progress() {
+            return Err(LobbyServiceError::new(
+                ErrorKind::InvalidAction,
+                "A game is already in progress",
+            )
+            .into());
+        }
+
+        let mut modified_lobby = current_lobby.clone();
+        for player in modified_lobby
+            .players
+            .iter_mut()
+            .filter(|p| &p.connection_id == connection_id)
+        {
+            player.ready = ready;
+        }
+        let started = modified_lobby.auto_start && modified_lobby.can_start();
+        if started {
+            modified_lobby.deal();
+        }
+        modified_lobby.updated_at = *now;
+
+        let new_lobby = repo
+            .update(
+                &current_lobby.id,
+                &current_lobby.updated_at,
+                &modified_lobby,
+            )
+            .await?;
+
+        Ok((new_lobby, started))
+    }
+
+    /// Deal games as soon as everyone at the table is ready, or stop, if `connection_id` is the
+    /// host's.
+    ///
+    /// Also returns whether that dealt a game, since everyone may be ready already.
+    pub async fn set_auto_start(
+        repo: &dyn LobbyRepo,
+        now: &DateTime<Utc>,
+        lobby_id: &LobbyId,
+        connection_id: &String,
+        auto_start: bool,
+    ) -> Result<(Lobby, bool), Box<dyn std::error::Error + Sync + Send + 'static>> {
+        log::info!("Auto start: {} {}", lobby_id, auto_start);
+
+        let current_lobby = Self::get(repo, lobby_id).await?;
+        if !current_lobby.is_host(connection_id) {
+            return Err(LobbyServiceError::new(
+                ErrorKind::InvalidAction,
+                "Only the host can change how games start",
+            )
+            .into());
+        }
+
+        let mut modified_lobby = current_lobby.clone();
+        modified_lobby.auto_start = auto_start;
+        let started = auto_start && modified_lobby.can_start();
+        if started {
+            modified_lobby.deal();
+        }
+        modified_lobby.updated_at = *now;
+
+        let new_lobby = repo
+            .update(
+                &current_lobby.id,
+                &current_lobby.updated_at,
+                &modified_lobby,
+            )
+            .await?;
+
+        Ok((new_lobby, started))
+    }
+
+    /// Play a card for the player with `connection_id`, dealing the next hand if it was the last.
+    ///
+    /// Also returns what the card led to, so callers can tell when a hand was scored.
+    pub async fn play_card(
+        repo: &dyn LobbyRepo,
+        now: &DateTime<Utc>,
+        lobby_id: &LobbyId,
+        connection_id: &String,
+        card: game::Card,
+    ) -> Result<(Lobby, Played), Box<dyn std::error::Error + Sync + Send + 'static>> {
+        log::info!("Play: {} {}", lobby_id, card);
+
+        let current_lobby = Self::get(repo, lobby_id).await?;
+        let seat = current_lobby.seat_of(connection_id).ok_or_else(|| {
+            LobbyServiceError::new(ErrorKind::InvalidAction, "You are not at the table")
+        })?;
+
+        let mut modified_lobby = current_lobby.clone();
+        let GameState(game) = modified_lobby.game.as_mut().ok_or_else(|| {
+            LobbyServiceError::new(ErrorKind::InvalidAction, "No game has been started")
+        })?;
+        let played = game
+            .play(seat, card)
+            .map_err(|e| LobbyServiceError::new(ErrorKind::IllegalPlay, &e.to_string()))?;
+        if played == Played::Hand && !game.is_finished() {
+            game.deal(shuffled_deck());
+        }
+        modified_lobby.updated_at = *now;
+
+        let new_lobby = repo
+            .update(
+                &current_lobby.id,
+                &current_lobby.updated_at,
+                &modified_lobby,
+            )
+            .await?;
+
+        Ok((new_lobby, played))
+    }
+
+    /// Move the player with `session_token` over to `connection_id`, keeping their seat.
+    pub async fn resume(
+        repo: &dyn LobbyRepo,
+        now: &DateTime<Utc>,
+        lobby_id: &LobbyId,
+        session_token: &String,
+        connection_id: &String,
+    ) -> Result<Lobby, Box<dyn std::error::Error + Sync + Send + 'static>> {
+        log::info!("Resume: {}", lobby_id);
+
+        let current_lobby = Self::get(repo, lobby_id).await?;
+        let mut modified_lobby = current_lobby.clone();
+        let player = modified_lobby
+            .players
+            .iter_mut()
+            .find(|p| !p.session_token.is_empty() && &p.session_token == session_token)
+            .ok_or_else(|| {
+                LobbyServiceError::new(
+                    ErrorKind::PlayerNotFound,
+                    "Your session is not part of this lobby",
+                )
+            })?;
+        if modified_lobby.host == player.connection_id {
+            modified_lobby.host = connection_id.to_string();
+        }
+        for seat in modified_lobby
+            .seats
+            .iter_mut()
+            .filter(|c| c == &&player.connection_id)
+        {
+            *seat = connection_id.to_string();
+        }
+        player.connection_id = connection_id.to_string();
+        player.away = false;
+        modified_lobby.updated_at = *now;
+
+        let new_lobby = repo
+            .update(
+                &current_lobby.id,
+                &current_lobby.updated_at,
+                &modified_lobby,
+            )
+            .await?;
+
+        Ok(new_lobby)
+    }
+
+    /// Choose the cards the player with `connection_id` passes this hand.
+    pub async fn pass_cards(
+        repo: &dyn LobbyRepo,
+        now: &DateTime<Utc>,
+        lobby_id: &LobbyId,
+        connection_id: &String,
+        cards: Vec<game::Card>,
+    ) -> Result<Lobby, Box<dyn std::error::Error + Sync + Send + 'static>> {
+        log::info!("Pass: {} {:?}", lobby_id, cards);
+
+        let current_lobby = Self::get(repo, lobby_id).await?;
+        let seat = current_lobby.seat_of(connection_id).ok_or_else(|| {
+            LobbyServiceError::new(ErrorKind::InvalidAction, "You are not at the table")
+        })?;
+
+        let mut modified_lobby = current_lobby.clone();
+        let GameState(game) = modified_lobby.game.as_mut().ok_or_else(|| {
+            LobbyServiceError::new(ErrorKind::InvalidAction, "No game has been started")
+        })?;
+        game.pass(seat, cards)
+            .map_err(|e| LobbyServiceError::new(ErrorKind::IllegalPlay, &e.to_string()))?;
+        modified_lobby.updated_at = *now;
 
-        return Ok(new_lobby);
+        let new_lobby = repo
+            .update(
+                &current_lobby.id,
+                &current_lobby.updated_at,
+                &modified_lobby,
+            )
+            .await?;
+
+        Ok(new_lobby)
+    }
+
+    /// Keep a chat message from the player with `connection_id`, dropping expired and excess ones.
+    ///
+    /// The message is only for `to` if it is given, who must be in the lobby too. It is refused if
+    /// the sender is muted, sending too quickly, or it is too long or has a word in `filter`.
+    pub async fn post_message(
+        repo: &dyn LobbyRepo,
+        now: &DateTime<Utc>,
+        lobby_id: &LobbyId,
+        connection_id: &String,
+        body: String,
+        to: Option<&Recipient>,
+        filter: &WordFilter,
+    ) -> Result<(Lobby, ChatMessage), Box<dyn std::error::Error + Sync + Send + 'static>> {
+        log::info!("Message: {}", lobby_id);
+
+        let current_lobby = Self::get(repo, lobby_id).await?;
+        let visible_to = current_lobby.recipients(connection_id, to)?;
+        let mut modified_lobby = current_lobby.clone();
+        let sender = modified_lobby
+            .players
+            .iter_mut()
+            .find(|p| &p.connection_id == connection_id)
+            .ok_or_else(|| {
+                LobbyServiceError::new(ErrorKind::PlayerNotFound, "You are not in this lobby")
+            })?;
+        sender.check_not_muted()?;
+        if body.trim().is_empty() {
+            return Err(LobbyServiceError::new(
+                ErrorKind::InvalidMessage,
+                "Chat messages can't be empty",
+            )
+            .into());
+        }
+        if body.chars().count() > messages::CHAT_MAX_LENGTH {
+            return Err(LobbyServiceError::new(
+                ErrorKind::MessageTooLong,
+                &format!(
+                    "Chat messages can be at most {} characters",
+                    messages::CHAT_MAX_LENGTH
+                ),
+            )
+            .into());
+        }
+        if let Some(word) = filter.blocked_word(&body) {
+            return Err(LobbyServiceError::new(
+                ErrorKind::BlockedWord,
+                &format!("\"{}\" isn't allowed in chat", word),
+            )
+            .into());
+        }
+        sender.count_message(now)?;
+
+        let message = ChatMessage {
+            id: nanoid!(12),
+            sent_at: *now,
+            name: sender.name.clone(),
+            body,
+            to: match to {
+                Some(Recipient::Player { name }) => Some(name.clone()),
+                _ => None,
+            },
+            team: to == Some(&Recipient::Team),
+            visible_to,
+            system: false,
+        };
+        modified_lobby.keep_message(now, message.clone());
+        modified_lobby.updated_at = *now;
+
+        let new_lobby = repo
+            .update(
+                &current_lobby.id,
+                &current_lobby.updated_at,
+                &modified_lobby,
+            )
+            .await?;
+
+        Ok((new_lobby, message))
+    }
+
+    /// Keep something the server says in the chat, for everyone or only the player with
+    /// `connection_id` if it is given.
+    pub async fn post_system_message(
+        repo: &dyn LobbyRepo,
+        now: &DateTime<Utc>,
+        lobby_id: &LobbyId,
+        body: String,
+        connection_id: Option<&String>,
+    ) -> Result<(Lobby, ChatMessage), Box<dyn std::error::Error + Sync + Send + 'static>> {
+        log::info!("System message: {}", lobby_id);
+
+        let current_lobby = Self::get(repo, lobby_id).await?;
+        let visible_to = match connection_id {
+            Some(connection_id) => {
+                let player = current_lobby.player(connection_id).ok_or_else(|| {
+                    LobbyServiceError::new(ErrorKind::PlayerNotFound, "You are not in this lobby")
+                })?;
+                vec![player.id.clone()]
+            }
+            None => Vec::new(),
+        };
+        let message = ChatMessage {
+            id: nanoid!(12),
+            sent_at: *now,
+            name: String::new(),
+            body,
+            to: None,
+            team: false,
+            visible_to,
+            system: true,
+        };
+
+        let mut modified_lobby = current_lobby.clone();
+        modified_lobby.keep_message(now, message.clone());
+        modified_lobby.updated_at = *now;
+
+        let new_lobby = repo
+            .update(
+                &current_lobby.id,
+                &current_lobby.updated_at,
+                &modified_lobby,
+            )
+            .await?;
+
+        Ok((new_lobby, message))
+    }
+
+    /// Count a chat command or reaction from the player with `connection_id` like a chat message,
+    /// refusing it if they are muted or sending too quickly.
+    pub async fn moderate(
+        repo: &dyn LobbyRepo,
+        now: &DateTime<Utc>,
+        lobby_id: &LobbyId,
+        connection_id: &String,
+    ) -> Result<Lobby, Box<dyn std::error::Error + Sync + Send + 'static>> {
+        log::info!("Moderate: {}", lobby_id);
+
+        let current_lobby = Self::get(repo, lobby_id).await?;
+        let mut modified_lobby = current_lobby.clone();
+        let sender = modified_lobby
+            .players
+            .iter_mut()
+            .find(|p| &p.connection_id == connection_id)
+            .ok_or_else(|| {
+                LobbyServiceError::new(ErrorKind::PlayerNotFound, "You are not in this lobby")
+            })?;
+        sender.check_not_muted()?;
+        sender.count_message(now)?;
+        modified_lobby.updated_at = *now;
+
+        let new_lobby = repo
+            .update(
+                &current_lobby.id,
+                &current_lobby.updated_at,
+                &modified_lobby,
+            )
+            .await?;
+
+        Ok(new_lobby)
+    }
+
+    /// Stop the player called `name` from chatting, or let them again, if `connection_id` is the
+    /// host's.
+    pub async fn mute_player(
+        repo: &dyn LobbyRepo,
+        now: &DateTime<Utc>,
+        lobby_id: &LobbyId,
+        connection_id: &String,
+        name: &String,
+        muted: bool,
+    ) -> Result<Lobby, Box<dyn std::error::Error + Sync + Send + 'static>> {
+        log::info!("Mute: {} {} {}", lobby_id, name, muted);
+
+        let current_lobby = Self::get(repo, lobby_id).await?;
+        if !current_lobby.is_host(connection_id) {
+            return Err(LobbyServiceError::new(
+                ErrorKind::InvalidAction,
+                "Only the host can mute players",
+            )
+            .into());
+        }
+
+        let mut modified_lobby = current_lobby.clone();
+        let player = modified_lobby
+            .players
+            .iter_mut()
+            .find(|p| &p.name == name)
+            .ok_or_else(|| {
+                LobbyServiceError::new(
+                    ErrorKind::PlayerNotFound,
+                    "Nobody in the lobby has that name",
+                )
+            })?;
+        player.muted = muted;
+        modified_lobby.updated_at = *now;
+
+        let new_lobby = repo
+            .update(
+                &current_lobby.id,
+                &current_lobby.updated_at,
+                &modified_lobby,
+            )
+            .await?;
+
+        Ok(new_lobby)
+    }
+
+    /// Remove the player called `name` from the lobby, if `connection_id` is the host's.
+    ///
+    /// Also returns the removed player, so they can be told.
+    pub async fn kick_player(
+        repo: &dyn LobbyRepo,
+        now: &DateTime<Utc>,
+        lobby_id: &LobbyId,
+        connection_id: &String,
+        name: &String,
+    ) -> Result<(Lobby, Player), Box<dyn std::error::Error + Sync + Send + 'static>> {
+        log::info!("Kick: {} {}", lobby_id, name);
+
+        let current_lobby = Self::get(repo, lobby_id).await?;
+        if !current_lobby.is_host(connection_id) {
+            return Err(LobbyServiceError::new(
+                ErrorKind::InvalidAction,
+                "Only the host can remove players",
+            )
+            .into());
+        }
+        let index = current_lobby
+            .players
+            .iter()
+            .position(|p| &p.name == name)
+            .ok_or_else(|| {
+                LobbyServiceError::new(
+                    ErrorKind::PlayerNotFound,
+                    "Nobody in the lobby has that name",
+                )
+            })?;
+        if &current_lobby.players[index].connection_id == connection_id {
+            return Err(LobbyServiceError::new(
+                ErrorKind::InvalidAction,
+                "The host can't remove themselves",
+            )
+            .into());
+        }
+
+        let mut modified_lobby = current_lobby.clone();
+        let removed = modified_lobby.remove_player(index)?;
+        modified_lobby.updated_at = *now;
+
+        let new_lobby = repo
+            .update(
+                &current_lobby.id,
+                &current_lobby.updated_at,
+                &modified_lobby,
+            )
+            .await?;
+
+        Ok((new_lobby, removed))
+    }
+
+    /// Take the player with `connection_id` out of the lobby, handing the host on if it was them.
+    pub async fn leave(
+        repo: &dyn LobbyRepo,
+        now: &DateTime<Utc>,
+        lobby_id: &LobbyId,
+        connection_id: &String,
+    ) -> Result<(Lobby, Player), Box<dyn std::error::Error + Sync + Send + 'static>> {
+        log::info!("Leave: {}", lobby_id);
+
+        let current_lobby = Self::get(repo, lobby_id).await?;
+        let index = current_lobby
+            .players
+            .iter()
+            .position(|p| &p.connection_id == connection_id)
+            .ok_or_else(|| {
+                LobbyServiceError::new(ErrorKind::PlayerNotFound, "You are not in this lobby")
+            })?;
+
+        let mut modified_lobby = current_lobby.clone();
+        let removed = modified_lobby.remove_player(index)?;
+        modified_lobby.updated_at = *now;
+
+        let new_lobby = repo
+            .update(
+                &current_lobby.id,
+                &current_lobby.updated_at,
+                &modified_lobby,
+            )
+            .await?;
+
+        Ok((new_lobby, removed))
+    }
+
+    /// Mark the player with `connection_id` as away, handing the host on if it was them and
+    /// someone else is still connected.
+    ///
+    /// Returns `None` if nobody in the lobby had the connection any more.
+    pub async fn disconnect(
+        repo: &dyn LobbyRepo,
+        now: &DateTime<Utc>,
+        lobby_id: &LobbyId,
+        connection_id: &String,
+    ) -> Result<Option<Lobby>, Box<dyn std::error::Error + Sync + Send + 'static>> {
+        log::info!("Disconnect: {}", lobby_id);
+
+        let current_lobby = Self::get(repo, lobby_id).await?;
+        let mut modified_lobby = current_lobby.clone();
+        match modified_lobby
+            .players
+            .iter_mut()
+            .find(|p| &p.connection_id == connection_id)
+        {
+            Some(player) => player.away = true,
+            None => return Ok(None),
+        }
+        if modified_lobby.is_host(connection_id) {
+            if let Some(next) = modified_lobby.next_host(connection_id).filter(|p| !p.away) {
+                modified_lobby.host = next.connection_id.clone();
+            }
+        }
+        modified_lobby.updated_at = *now;
+
+        let new_lobby = repo
+            .update(
+                &current_lobby.id,
+                &current_lobby.updated_at,
+                &modified_lobby,
+            )
+            .await?;
+
+        Ok(Some(new_lobby))
+    }
+
+    /// Make the player called `name` the host, if `connection_id` is the host's.
+    pub async fn transfer_host(
+        repo: &dyn LobbyRepo,
+        now: &DateTime<Utc>,
+        lobby_id: &LobbyId,
+        connection_id: &String,
+        name: &String,
+    ) -> Result<Lobby, Box<dyn std::error::Error + Sync + Send + 'static>> {
+        log::info!("Transfer host: {} {}", lobby_id, name);
+
+        let current_lobby = Self::get(repo, lobby_id).await?;
+        if !current_lobby.is_host(connection_id) {
+            return Err(LobbyServiceError::new(
+                ErrorKind::InvalidAction,
+                "Only the host can hand over the lobby",
+            )
+            .into());
+        }
+        let next = current_lobby
+            .players
+            .iter()
+            .find(|p| &p.name == name)
+            .ok_or_else(|| {
+                LobbyServiceError::new(
+                    ErrorKind::PlayerNotFound,
+                    "Nobody in the lobby has that name",
+                )
+            })?;
+
+        let mut modified_lobby = current_lobby.clone();
+        modified_lobby.host = next.connection_id.clone();
+        modified_lobby.updated_at = *now;
+
+        let new_lobby = repo
+            .update(
+                &current_lobby.id,
+                &current_lobby.updated_at,
+                &modified_lobby,
+            )
+            .await?;
+
+        Ok(new_lobby)
+    }
+
+    /// Stop anyone else joining the lobby, or let them again, if `connection_id` is the host's.
+    pub async fn set_locked(
+        repo: &dyn LobbyRepo,
+        now: &DateTime<Utc>,
+        lobby_id: &LobbyId,
+        connection_id: &String,
+        locked: bool,
+    ) -> Result<Lobby, Box<dyn std::error::Error + Sync + Send + 'static>> {
+        log::info!("Lock: {} {}", lobby_id, locked);
+
+        let current_lobby = Self::get(repo, lobby_id).await?;
+        if !current_lobby.is_host(connection_id) {
+            return Err(LobbyServiceError::new(
+                ErrorKind::InvalidAction,
+                "Only the host can lock the lobby",
+            )
+            .into());
+        }
+
+        let mut modified_lobby = current_lobby.clone();
+        modified_lobby.locked = locked;
+        modified_lobby.updated_at = *now;
+
+        let new_lobby = repo
+            .update(
+                &current_lobby.id,
+                &current_lobby.updated_at,
+                &modified_lobby,
+            )
+            .await?;
+
+        Ok(new_lobby)
     }
 
     pub async fn get(
-        ddb: &DynamoDbClient,
+        repo: &dyn LobbyRepo,
         lobby_id: &LobbyId,
     ) -> Result<Lobby, Box<dyn std::error::Error + Sync + Send + 'static>> {
         log::info!("Get: {}", lobby_id);
 
-        let lobby = LobbyRepo::get(ddb, lobby_id)
-            .await?
-            .expect("Could not get lobby");
+        let lobby = repo.get(lobby_id).await?.ok_or_else(|| {
+            LobbyServiceError::new(ErrorKind::LobbyNotFound, "Could not get Lobby")
+        })?;
         log::info!("LobbyService::get result: {:?}", &lobby);
 
-        return Ok(lobby);
+        Ok(lobby)
     }
 }
 
-struct LobbyRepo;
+fn shuffled_deck() -> Vec<game::Card> {
+    let mut deck = game::cards::deck();
+    deck.shuffle(&mut rand::thread_rng());
+    deck
+}
 
-impl LobbyRepo {
-    /// Retrieve a single Lobby by Id.
-    pub async fn get(
-        ddb: &DynamoDbClient,
-        lobby_code: &LobbyId,
-    ) -> Result<Option<Lobby>, Box<dyn std::error::Error + Sync + Send + 'static>> {
-        let table_name = env::var("tableName")?;
-        let maybe_lobby = ddb
-            .get_item(GetItemInput {
-                table_name: table_name.clone(),
-                key: {
-                    let mut x = HashMap::new();
-                    x.insert(
-                        "id".to_owned(),
-                        AttributeValue {
-                            s: Some(lobby_code.to_string()),
-                            ..AttributeValue::default()
-                        },
-                    );
-                    x
-                },
-                ..GetItemInput::default()
-            })
-            .await?
-            .item
-            .map(|attrs| Lobby::from_attrs(attrs))
-            .transpose()?;
-
-        log::info!("LobbyRepo::get result: {:?}", &maybe_lobby);
-        return Ok(maybe_lobby);
-    }
-
-    /// Put new Lobby into a table.
-    pub async fn put(
-        ddb: &DynamoDbClient,
-        lobby: &Lobby,
-    ) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
-        let item = lobby.clone().into();
-        let table_name = env::var("tableName")?;
-        let result = ddb
-            .put_item(PutItemInput {
-                table_name: table_name.clone(),
-                item,
-                ..PutItemInput::default()
-            })
-            .await?;
-        log::info!("LobbyRepo::put result: {:?}", result);
-        return Ok(());
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn create_stores_lobby_with_host() {
+        let repo = InMemoryLobbyRepo::new();
+        let now = Utc::now();
+
+        let lobby = LobbyService::create(
+            &repo,
+            &now,
+            &"Host".to_string(),
+            &"host-conn".to_string(),
+            &LobbyCodes::default(),
+        )
+        .await
+        .expect("failed to create lobby");
+
+        let stored = LobbyService::get(&repo, &lobby.id)
+            .await
+            .expect("failed to get lobby");
+        assert_eq!(stored.id.len(), 4);
+        assert_eq!(stored.players.len(), 1);
+        assert_eq!(stored.players[0].name, "Host");
+        assert_eq!(stored.players[0].connection_id, "host-conn");
     }
 
-    /// Update an existing Lobby.
-    ///
-    /// This function uses the `timestamp` of the passed lobby as the most recent timestamp and the
-    /// function will error if the table has a different value.
-    pub async fn update(
-        ddb: &DynamoDbClient,
-        lobby_id: &LobbyId,
-        previous_updated_at: &DateTime<Utc>,
+    #[tokio::test]
+    async fn create_never_reuses_a_taken_code() {
+        let repo = InMemoryLobbyRepo::new();
+        let now = Utc::now();
+        let codes = LobbyCodes::new(1);
+        let taken = LobbyService::create(
+            &repo,
+            &now,
+            &"Host".to_string(),
+            &"host-conn".to_string(),
+            &codes,
+        )
+        .await
+        .expect("failed to create lobby");
+        for c in LOBBY_CODE_ALPHABET.iter() {
+            let mut lobby = taken.clone();
+            lobby.id = c.to_string();
+            repo.create(&lobby).await.unwrap();
+        }
+
+        // With every one character code in use, no number of retries finds a free one.
+        let result = LobbyService::create(
+            &repo,
+            &now,
+            &"Late".to_string(),
+            &"late-conn".to_string(),
+            &codes,
+        )
+        .await;
+        assert_eq!(error_kind(result.unwrap_err()), ErrorKind::TryAgain);
+        let stored = LobbyService::get(&repo, &taken.id).await.unwrap();
+        assert_eq!(stored.players[0].name, "Host");
+    }
+
+    #[tokio::test]
+    async fn join_adds_player_to_lobby() {
+        let repo = InMemoryLobbyRepo::new();
+        let now = Utc::now();
+        let lobby = LobbyService::create(
+            &repo,
+            &now,
+            &"Host".to_string(),
+            &"host-conn".to_string(),
+            &LobbyCodes::default(),
+        )
+        .await
+        .expect("failed to create lobby");
+
+        let later = now + chrono::Duration::seconds(1);
+        let joined = LobbyService::join(
+            &repo,
+            &later,
+            &lobby.id,
+            &"Amigo".to_string(),
+            &"amigo-conn".to_string(),
+        )
+        .await
+        .expect("failed to join lobby");
+
+        let names: Vec<&str> = joined.players.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["Host", "Amigo"]);
+        assert_eq!(joined.updated_at, later);
+    }
+
+    async fn join_as(
+        repo: &InMemoryLobbyRepo,
         lobby: &Lobby,
+        name: &str,
+        connection_id: &str,
     ) -> Result<Lobby, Box<dyn std::error::Error + Sync + Send + 'static>> {
-        let item = lobby.clone().into();
-        let table_name = env::var("tableName")?;
-        log::debug!(
-            "LobbyRepo::update updated_at ({}), previous ({})",
-            &lobby.updated_at,
-            previous_updated_at,
+        LobbyService::join(
+            repo,
+            &Utc::now(),
+            &lobby.id,
+            &name.to_string(),
+            &connection_id.to_string(),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn join_checks_and_numbers_names() {
+        let repo = InMemoryLobbyRepo::new();
+        let lobby = lobby_with_players(&repo, 1).await;
+
+        let blank = join_as(&repo, &lobby, "   ", "blank-conn").await;
+        assert_eq!(error_kind(blank.unwrap_err()), ErrorKind::InvalidName);
+        join_as(&repo, &lobby, " player  0 ", "twin-conn")
+            .await
+            .expect("failed to join lobby");
+        let joined = join_as(&repo, &lobby, "Player 0", "triplet-conn")
+            .await
+            .expect("failed to join lobby");
+
+        let names: Vec<&str> = joined.players.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["Player 0", "player 0 (2)", "Player 0 (3)"]);
+    }
+
+    #[tokio::test]
+    async fn join_unknown_lobby_is_an_error() {
+        let repo = InMemoryLobbyRepo::new();
+
+        let result = LobbyService::join(
+            &repo,
+            &Utc::now(),
+            &"nope".to_string(),
+            &"Amigo".to_string(),
+            &"amigo-conn".to_string(),
+        )
+        .await;
+
+        assert!(result.is_err());
+    }
+
+    async fn lobby_with_players(
+        repo: &InMemoryLobbyRepo,
+        count: usize,
+    ) -> Lobby {
+        let now = Utc::now();
+        let mut lobby = LobbyService::create(
+            repo,
+            &now,
+            &"Player 0".to_string(),
+            &"conn-0".to_string(),
+            &LobbyCodes::default(),
+        )
+        .await
+        .expect("failed to create lobby");
+        for i in 1..count {
+            lobby = LobbyService::join(
+                repo,
+                &now,
+                &lobby.id,
+                &format!("Player {}", i),
+                &format!("conn-{}", i),
+            )
+            .await
+            .expect("failed to join lobby");
+        }
+        lobby
+    }
+
+    async fn ready_everyone(
+        repo: &InMemoryLobbyRepo,
+        lobby: &Lobby,
+    ) {
+        for seat in 0..lobby.players.len().min(game::PLAYERS) {
+            LobbyService::set_ready(
+                repo,
+                &Utc::now(),
+                &lobby.id,
+                &format!("conn-{}", seat),
+                true,
+            )
+            .await
+            .expect("failed to get ready");
+        }
+    }
+
+    fn error_kind(error: Box<dyn std::error::Error + Sync + Send + 'static>) -> ErrorKind {
+        error
+            .downcast_ref::<LobbyServiceError>()
+            .expect("not a LobbyServiceError")
+            .to_response()
+            .kind
+    }
+
+    #[tokio::test]
+    async fn start_game_needs_four_players() {
+        let repo = InMemoryLobbyRepo::new();
+        let lobby = lobby_with_players(&repo, 3).await;
+
+        let result =
+            LobbyService::start_game(&repo, &Utc::now(), &lobby.id, &"conn-0".to_string()).await;
+
+        assert_eq!(error_kind(result.unwrap_err()), ErrorKind::InvalidAction);
+    }
+
+    #[tokio::test]
+    async fn start_game_waits_for_everyone_to_be_ready() {
+        let repo = InMemoryLobbyRepo::new();
+        let lobby = lobby_with_players(&repo, 4).await;
+        for seat in 0..3 {
+            LobbyService::set_ready(
+                &repo,
+                &Utc::now(),
+                &lobby.id,
+                &format!("conn-{}", seat),
+                true,
+            )
+            .await
+            .expect("failed to get ready");
+        }
+
+        let result =
+            LobbyService::start_game(&repo, &Utc::now(), &lobby.id, &"conn-0".to_string()).await;
+        assert_eq!(error_kind(result.unwrap_err()), ErrorKind::NotReady);
+
+        let (_, started) = LobbyService::set_auto_start(
+            &repo,
+            &Utc::now(),
+            &lobby.id,
+            &"conn-0".to_string(),
+            true,
+        )
+        .await
+        .expect("failed to start automatically");
+        assert!(!started);
+        let (lobby, started) =
+            LobbyService::set_ready(&repo, &Utc::now(), &lobby.id, &"conn-3".to_string(), true)
+                .await
+                .expect("failed to get ready");
+        assert!(started);
+        assert!(lobby.game.is_some());
+        assert_eq!(lobby.not_ready().len(), game::PLAYERS);
+
+        let result =
+            LobbyService::set_ready(&repo, &Utc::now(), &lobby.id, &"conn-0".to_string(), true)
+                .await;
+        assert_eq!(error_kind(result.unwrap_err()), ErrorKind::InvalidAction);
+    }
+
+    #[tokio::test]
+    async fn players_choose_seats_the_game_is_played_in() {
+        let repo = InMemoryLobbyRepo::new();
+        let lobby = lobby_with_players(&repo, 3).await;
+        assert_eq!(lobby.seat_of(&"conn-2".to_string()), Some(2));
+
+        let lobby =
+            LobbyService::take_seat(&repo, &Utc::now(), &lobby.id, &"conn-2".to_string(), 3)
+                .await
+                .expect("failed to take seat");
+        assert_eq!(lobby.seat_of(&"conn-2".to_string()), Some(3));
+        let lobby = LobbyService::join(
+            &repo,
+            &Utc::now(),
+            &lobby.id,
+            &"Player 3".to_string(),
+            &"conn-3".to_string(),
+        )
+        .await
+        .expect("failed to join lobby");
+        assert_eq!(lobby.seat_of(&"conn-3".to_string()), Some(2));
+
+        let taken =
+            LobbyService::take_seat(&repo, &Utc::now(), &lobby.id, &"conn-3".to_string(), 0).await;
+        assert_eq!(error_kind(taken.unwrap_err()), ErrorKind::InvalidAction);
+        let not_host =
+            LobbyService::swap_seats(&repo, &Utc::now(), &lobby.id, &"conn-1".to_string(), 0, 1)
+                .await;
+        assert_eq!(error_kind(not_host.unwrap_err()), ErrorKind::InvalidAction);
+        let lobby =
+            LobbyService::swap_seats(&repo, &Utc::now(), &lobby.id, &"conn-0".to_string(), 0, 3)
+                .await
+                .expect("failed to swap seats");
+        assert_eq!(lobby.seated(0).unwrap().name, "Player 2");
+        assert_eq!(lobby.seated(3).unwrap().name, "Player 0");
+
+        ready_everyone(&repo, &lobby).await;
+        let lobby = LobbyService::start_game(&repo, &Utc::now(), &lobby.id, &"conn-0".to_string())
+            .await
+            .expect("failed to start game");
+        let cards = lobby.game.as_ref().unwrap().0.hands[0][..3].to_vec();
+        let lobby =
+            LobbyService::pass_cards(&repo, &Utc::now(), &lobby.id, &"conn-2".to_string(), cards)
+                .await
+                .expect("failed to pass cards");
+        assert!(lobby.game.as_ref().unwrap().0.has_passed(0));
+    }
+
+    #[tokio::test]
+    async fn passing_waits_for_every_player() {
+        let repo = InMemoryLobbyRepo::new();
+        let lobby = lobby_with_players(&repo, 4).await;
+        ready_everyone(&repo, &lobby).await;
+        let mut lobby =
+            LobbyService::start_game(&repo, &Utc::now(), &lobby.id, &"conn-0".to_string())
+                .await
+                .expect("failed to start game");
+
+        for seat in 0..game::PLAYERS {
+            let game = &lobby.game.as_ref().unwrap().0;
+            assert_eq!(game.phase, game::Phase::Passing);
+            let cards = game.hands[seat][..3].to_vec();
+            lobby = LobbyService::pass_cards(
+                &repo,
+                &Utc::now(),
+                &lobby.id,
+                &format!("conn-{}", seat),
+                cards,
+            )
+            .await
+            .expect("failed to pass cards");
+        }
+
+        let game = &lobby.game.as_ref().unwrap().0;
+        assert_eq!(game.phase, game::Phase::Playing);
+        let result = LobbyService::pass_cards(
+            &repo,
+            &Utc::now(),
+            &lobby.id,
+            &"conn-0".to_string(),
+            game.hands[0][..3].to_vec(),
+        )
+        .await;
+        assert_eq!(error_kind(result.unwrap_err()), ErrorKind::IllegalPlay);
+    }
+
+    #[tokio::test]
+    async fn play_card_enforces_the_rules() {
+        let repo = InMemoryLobbyRepo::new();
+        let lobby = lobby_with_players(&repo, 4).await;
+        ready_everyone(&repo, &lobby).await;
+        let mut lobby =
+            LobbyService::start_game(&repo, &Utc::now(), &lobby.id, &"conn-0".to_string())
+                .await
+                .expect("failed to start game");
+        for seat in 0..game::PLAYERS {
+            let cards = lobby.game.as_ref().unwrap().0.hands[seat][..3].to_vec();
+            lobby = LobbyService::pass_cards(
+                &repo,
+                &Utc::now(),
+                &lobby.id,
+                &format!("conn-{}", seat),
+                cards,
+            )
+            .await
+            .expect("failed to pass cards");
+        }
+        let game = &lobby.game.as_ref().unwrap().0;
+        let leader = format!("conn-{}", game.turn);
+        let other_card = game.hands[game.turn]
+            .iter()
+            .copied()
+            .find(|&c| c != game::Card::TWO_OF_CLUBS)
+            .unwrap();
+
+        let result =
+            LobbyService::play_card(&repo, &Utc::now(), &lobby.id, &leader, other_card).await;
+        assert_eq!(error_kind(result.unwrap_err()), ErrorKind::IllegalPlay);
+
+        let (lobby, played) = LobbyService::play_card(
+            &repo,
+            &Utc::now(),
+            &lobby.id,
+            &leader,
+            game::Card::TWO_OF_CLUBS,
+        )
+        .await
+        .expect("failed to play the two of clubs");
+        let game = &lobby.game.as_ref().unwrap().0;
+        assert_eq!(played, Played::Card);
+        assert_eq!(game.trick.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn chat_history_is_bounded_and_paged() {
+        let repo = InMemoryLobbyRepo::new();
+        let lobby = lobby_with_players(&repo, 1).await;
+        let start = Utc::now();
+
+        let stale = LobbyService::post_message(
+            &repo,
+            &start,
+            &lobby.id,
+            &"conn-0".to_string(),
+            "stale".to_string(),
+            None,
+            &WordFilter::default(),
+        )
+        .await
+        .expect("failed to post message");
+        let later = start + chrono::Duration::hours(CHAT_TTL_HOURS + 1);
+        let mut lobby = stale.0;
+        for i in 0..CHAT_HISTORY_LIMIT + 10 {
+            let now = later + chrono::Duration::seconds(3 * i as i64);
+            lobby = LobbyService::post_message(
+                &repo,
+                &now,
+                &lobby.id,
+                &"conn-0".to_string(),
+                format!("message {}", i),
+                None,
+                &WordFilter::default(),
+            )
+            .await
+            .expect("failed to post message")
+            .0;
+        }
+
+        assert_eq!(lobby.chat.len(), CHAT_HISTORY_LIMIT);
+        assert_eq!(lobby.chat[0].body, "message 10");
+        let viewer = lobby.players[0].clone();
+        let (latest, more) = lobby.chat_page(&lobby.updated_at, &viewer, None);
+        assert_eq!(latest.len(), CHAT_PAGE_SIZE);
+        assert_eq!(latest.last(), lobby.chat.last());
+        assert!(more);
+        let (oldest, more) = lobby.chat_page(&lobby.updated_at, &viewer, Some(&lobby.chat[30].id));
+        assert_eq!(oldest, lobby.chat[..30].to_vec());
+        assert!(!more);
+    }
+
+    #[tokio::test]
+    async fn chat_history_fits_in_a_lobby_item() {
+        let repo = InMemoryLobbyRepo::new();
+        let mut lobby = lobby_with_players(&repo, 1).await;
+        let start = Utc::now();
+        // Two bytes for every character, so each message is as big as a message can be.
+        let body = "é".repeat(messages::CHAT_MAX_LENGTH);
+        for i in 0..CHAT_HISTORY_BYTES / body.len() + 10 {
+            let now = start + chrono::Duration::seconds(3 * i as i64);
+            lobby = LobbyService::post_message(
+                &repo,
+                &now,
+                &lobby.id,
+                &"conn-0".to_string(),
+                body.clone(),
+                None,
+                &WordFilter::default(),
+            )
+            .await
+            .expect("failed to post message")
+            .0;
+        }
+
+        let bytes: usize = lobby.chat.iter().map(|m| m.body.len()).sum();
+        assert!(bytes <= CHAT_HISTORY_BYTES);
+        assert_eq!(lobby.chat.len(), CHAT_HISTORY_BYTES / body.len());
+        assert_eq!(lobby.chat.last().unwrap().sent_at, lobby.updated_at);
+    }
+
+    async fn post(
+        repo: &InMemoryLobbyRepo,
+        lobby: &Lobby,
+        connection_id: &str,
+        body: &str,
+    ) -> Result<(Lobby, ChatMessage), Box<dyn std::error::Error + Sync + Send + 'static>> {
+        let filter = WordFilter::new(vec!["darn".to_string()]);
+        LobbyService::post_message(
+            repo,
+            &Utc::now(),
+            &lobby.id,
+            &connection_id.to_string(),
+            body.to_string(),
+            None,
+            &filter,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn chat_is_moderated() {
+        let repo = InMemoryLobbyRepo::new();
+        let lobby = lobby_with_players(&repo, 2).await;
+
+        let too_long = "a".repeat(messages::CHAT_MAX_LENGTH + 1);
+        assert_eq!(
+            error_kind(post(&repo, &lobby, "conn-1", &too_long).await.unwrap_err()),
+            ErrorKind::MessageTooLong
         );
-        let result = ddb
-            .put_item(PutItemInput {
-                table_name: table_name.clone(),
-                condition_expression: Some("updated_at = :previousUpdatedAt".to_string()),
-                expression_attribute_values: Some(
-                    attr_map! { ":previousUpdatedAt" => previous_updated_at },
-                ),
-                item,
-                ..PutItemInput::default()
-            })
-            .await?;
-        log::info!("LobbyRepo::update result: {:?}", result);
+        assert_eq!(
+            error_kind(post(&repo, &lobby, "conn-1", "darn it").await.unwrap_err()),
+            ErrorKind::BlockedWord
+        );
+        for i in 0..CHAT_RATE_LIMIT {
+            post(&repo, &lobby, "conn-1", &format!("spam {}", i))
+                .await
+                .expect("failed to post message");
+        }
+        assert_eq!(
+            error_kind(
+                post(&repo, &lobby, "conn-1", "more spam")
+                    .await
+                    .unwrap_err()
+            ),
+            ErrorKind::RateLimited
+        );
+
+        let not_host = LobbyService::mute_player(
+            &repo,
+            &Utc::now(),
+            &lobby.id,
+            &"conn-1".to_string(),
+            &"Player 0".to_string(),
+            true,
+        )
+        .await;
+        assert_eq!(error_kind(not_host.unwrap_err()), ErrorKind::InvalidAction);
+        let muted = LobbyService::mute_player(
+            &repo,
+            &Utc::now(),
+            &lobby.id,
+            &"conn-0".to_string(),
+            &"Player 1".to_string(),
+            true,
+        )
+        .await
+        .expect("failed to mute player");
+        assert!(muted.players[1].muted);
+        assert_eq!(
+            error_kind(post(&repo, &lobby, "conn-1", "hello?").await.unwrap_err()),
+            ErrorKind::Muted
+        );
+        post(&repo, &lobby, "conn-0", "hello")
+            .await
+            .expect("the host is not muted");
+    }
 
-        Ok(LobbyRepo::get(ddb, lobby_id)
-            .await?
-            .expect("Could not get the record that was just updated"))
+    #[tokio::test]
+    async fn host_is_handed_on() {
+        let repo = InMemoryLobbyRepo::new();
+        let lobby = lobby_with_players(&repo, 3).await;
+        assert!(lobby.is_host(&"conn-0".to_string()));
+
+        let not_host = LobbyService::transfer_host(
+            &repo,
+            &Utc::now(),
+            &lobby.id,
+            &"conn-1".to_string(),
+            &"Player 1".to_string(),
+        )
+        .await;
+        assert_eq!(error_kind(not_host.unwrap_err()), ErrorKind::InvalidAction);
+
+        // The next host is whoever joined first of the players who are still here.
+        LobbyService::disconnect(&repo, &Utc::now(), &lobby.id, &"conn-1".to_string())
+            .await
+            .expect("failed to disconnect");
+        let lobby = LobbyService::disconnect(&repo, &Utc::now(), &lobby.id, &"conn-0".to_string())
+            .await
+            .expect("failed to disconnect")
+            .expect("conn-0 was in the lobby");
+        assert_eq!(lobby.host, "conn-2");
+        assert!(lobby.players[0].away);
+
+        let (lobby, left) =
+            LobbyService::leave(&repo, &Utc::now(), &lobby.id, &"conn-2".to_string())
+                .await
+                .expect("failed to leave");
+        assert_eq!(left.name, "Player 2");
+        assert_eq!(lobby.host, "conn-0");
+
+        let lobby = LobbyService::transfer_host(
+            &repo,
+            &Utc::now(),
+            &lobby.id,
+            &"conn-0".to_string(),
+            &"Player 1".to_string(),
+        )
+        .await
+        .expect("failed to transfer host");
+        assert_eq!(lobby.host, "conn-1");
+    }
+
+    #[tokio::test]
+    async fn locked_lobby_refuses_joins() {
+        let repo = InMemoryLobbyRepo::new();
+        let lobby = lobby_with_players(&repo, 2).await;
+
+        let kicked = LobbyService::kick_player(
+            &repo,
+            &Utc::now(),
+            &lobby.id,
+            &"conn-1".to_string(),
+            &"Player 0".to_string(),
+        )
+        .await;
+        assert_eq!(error_kind(kicked.unwrap_err()), ErrorKind::InvalidAction);
+
+        LobbyService::set_locked(&repo, &Utc::now(), &lobby.id, &"conn-0".to_string(), true)
+            .await
+            .expect("failed to lock lobby");
+        let joined = LobbyService::join(
+            &repo,
+            &Utc::now(),
+            &lobby.id,
+            &"Amigo".to_string(),
+            &"amigo-conn".to_string(),
+        )
+        .await;
+        assert_eq!(error_kind(joined.unwrap_err()), ErrorKind::LobbyLocked);
+
+        let (lobby, kicked) = LobbyService::kick_player(
+            &repo,
+            &Utc::now(),
+            &lobby.id,
+            &"conn-0".to_string(),
+            &"Player 1".to_string(),
+        )
+        .await
+        .expect("failed to kick player");
+        assert_eq!(kicked.connection_id, "conn-1");
+        assert_eq!(lobby.players.len(), 1);
     }
 }
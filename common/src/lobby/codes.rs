@@ -0,0 +1,61 @@
+use std::env;
+
+use nanoid::nanoid;
+
+/// The characters lobby codes are made of. Codes get read aloud, so `0`, `o`, `1`, `l` and `i`
+/// are left out to keep them from being mistaken for each other.
+pub const LOBBY_CODE_ALPHABET: [char; 31] = [
+    '2', '3', '4', '5', '6', '7', '8', '9', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'j', 'k', 'm',
+    'n', 'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z',
+];
+pub const DEFAULT_LOBBY_CODE_LENGTH: usize = 4;
+
+/// How the codes players join lobbies with are made.
+#[derive(Debug, Clone)]
+pub struct LobbyCodes {
+    length: usize,
+}
+
+impl LobbyCodes {
+    pub fn new(length: usize) -> Self {
+        LobbyCodes {
+            length: length.max(1),
+        }
+    }
+
+    /// Reads the code length from `LOBBY_CODE_LENGTH`, or uses `DEFAULT_LOBBY_CODE_LENGTH`.
+    pub fn from_env() -> Self {
+        match env::var("LOBBY_CODE_LENGTH").map(|length| length.parse::<usize>()) {
+            Ok(Ok(length)) => Self::new(length),
+            _ => Self::default(),
+        }
+    }
+
+    /// A random code, which may already belong to another lobby.
+    pub fn generate(&self) -> String {
+        let length = self.length;
+        nanoid!(length, &LOBBY_CODE_ALPHABET)
+    }
+}
+
+impl Default for LobbyCodes {
+    fn default() -> Self {
+        Self::new(DEFAULT_LOBBY_CODE_LENGTH)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_avoid_confusable_characters() {
+        let code = LobbyCodes::new(64).generate();
+
+        assert_eq!(code.chars().count(), 64);
+        assert!(code.chars().all(|c| LOBBY_CODE_ALPHABET.contains(&c)));
+        for confusable in ['0', 'o', '1', 'l', 'i'].iter() {
+            assert!(!LOBBY_CODE_ALPHABET.contains(confusable));
+        }
+    }
+}
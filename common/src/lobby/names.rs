@@ -0,0 +1,96 @@
+use messages::{ErrorKind, NAME_MAX_LENGTH};
+
+use super::LobbyServiceError;
+
+/// Why a name can't be used in a lobby.
+#[derive(Debug, PartialEq)]
+pub enum NameError {
+    /// Nothing is left of the name once whitespace is trimmed.
+    Empty,
+    /// The name has more than `NAME_MAX_LENGTH` characters.
+    TooLong,
+    /// The name contains a character other players might not be able to read or tell apart.
+    InvalidCharacter(char),
+}
+
+impl From<NameError> for LobbyServiceError {
+    fn from(error: NameError) -> Self {
+        let message = match error {
+            NameError::Empty => "Pick a name your friends will see".to_string(),
+            NameError::TooLong => {
+                format!("Names can be at most {} characters", NAME_MAX_LENGTH)
+            }
+            NameError::InvalidCharacter(c) => format!("Names can't contain `{}`", c),
+        };
+        LobbyServiceError::new(ErrorKind::InvalidName, &message)
+    }
+}
+
+/// `name` as other players will see it, with whitespace trimmed and runs of it collapsed.
+///
+/// Only letters, digits, spaces and `-_.'` are allowed, which also keeps anyone from picking a
+/// name that looks like one `disambiguate` made.
+pub fn validate(name: &str) -> Result<String, NameError> {
+    let name = name.split_whitespace().collect::<Vec<&str>>().join(" ");
+    if name.is_empty() {
+        return Err(NameError::Empty);
+    }
+    if name.chars().count() > NAME_MAX_LENGTH {
+        return Err(NameError::TooLong);
+    }
+    match name
+        .chars()
+        .find(|&c| !(c.is_alphanumeric() || c == ' ' || "-_.'".contains(c)))
+    {
+        Some(c) => Err(NameError::InvalidCharacter(c)),
+        None => Ok(name),
+    }
+}
+
+/// `name`, or `name (2)`, `name (3)` and so on if someone already has it, ignoring case.
+pub fn disambiguate(
+    name: String,
+    taken: &[&str],
+) -> String {
+    let is_taken = |candidate: &str| {
+        taken
+            .iter()
+            .any(|t| t.to_lowercase() == candidate.to_lowercase())
+    };
+    if !is_taken(&name) {
+        return name;
+    }
+    (2..)
+        .map(|n| format!("{} ({})", name, n))
+        .find(|candidate| !is_taken(candidate))
+        .expect("there are more numbers than players")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_trimmed_and_checked() {
+        assert_eq!(
+            validate("  Amigo   Grande "),
+            Ok("Amigo Grande".to_string())
+        );
+        assert_eq!(validate("O'Neil-Smith"), Ok("O'Neil-Smith".to_string()));
+        assert_eq!(validate(" \t "), Err(NameError::Empty));
+        assert_eq!(
+            validate(&"a".repeat(NAME_MAX_LENGTH + 1)),
+            Err(NameError::TooLong)
+        );
+        assert_eq!(validate("Host (2)"), Err(NameError::InvalidCharacter('(')));
+    }
+
+    #[test]
+    fn duplicate_names_are_numbered() {
+        assert_eq!(disambiguate("Amigo".to_string(), &["Host"]), "Amigo");
+        assert_eq!(
+            disambiguate("host".to_string(), &["Host", "Host (2)"]),
+            "host (3)"
+        );
+    }
+}
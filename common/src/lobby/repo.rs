@@ -0,0 +1,323 @@
+use std::collections::HashMap;
+use std::env;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use dynomite::{
+    attr_map,
+    dynamodb::{
+        DynamoDb, DynamoDbClient, GetItemInput, PutItemError, PutItemInput, UpdateItemInput,
+    },
+    AttributeValue, FromAttributes,
+};
+use rusoto_core::RusotoError;
+
+use super::{Lobby, LobbyId, LobbyServiceError};
+
+/// Storage for Lobbies.
+///
+/// `LobbyService` only talks to storage through this trait so that it can be backed by DynamoDB
+/// when deployed and by memory when testing or running without AWS.
+#[async_trait]
+pub trait LobbyRepo: Send + Sync {
+    /// Retrieve a single Lobby by Id.
+    async fn get(
+        &self,
+        lobby_id: &LobbyId,
+    ) -> Result<Option<Lobby>, Box<dyn std::error::Error + Sync + Send + 'static>>;
+
+    /// Put a new Lobby into the store.
+    ///
+    /// Returns `false`, leaving the store untouched, if a Lobby with the same id already exists.
+    async fn create(
+        &self,
+        lobby: &Lobby,
+    ) -> Result<bool, Box<dyn std::error::Error + Sync + Send + 'static>>;
+
+    /// Update an existing Lobby.
+    ///
+    /// The `previous_updated_at` is the timestamp the caller last read and the update must fail
+    /// with `LobbyServiceError::conflict` if the stored Lobby has a different value.
+    async fn update(
+        &self,
+        lobby_id: &LobbyId,
+        previous_updated_at: &DateTime<Utc>,
+        lobby: &Lobby,
+    ) -> Result<Lobby, Box<dyn std::error::Error + Sync + Send + 'static>>;
+
+    /// Remember which Lobby a connection is in, so it can be found when the connection closes.
+    async fn set_connection_lobby(
+        &self,
+        connection_id: &str,
+        lobby_id: &LobbyId,
+    ) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>>;
+
+    /// The Lobby a connection was last in.
+    async fn connection_lobby(
+        &self,
+        connection_id: &str,
+    ) -> Result<Option<LobbyId>, Box<dyn std::error::Error + Sync + Send + 'static>>;
+}
+
+pub struct DynamoDbLobbyRepo {
+    ddb: DynamoDbClient,
+    table_name: String,
+}
+
+impl DynamoDbLobbyRepo {
+    pub fn new(
+        ddb: DynamoDbClient,
+        table_name: String,
+    ) -> Self {
+        Self { ddb, table_name }
+    }
+
+    /// Create a repo for the table named by the `tableName` environment variable.
+    pub fn from_env(ddb: DynamoDbClient) -> Result<Self, env::VarError> {
+        let table_name = env::var("tableName")?;
+        Ok(Self::new(ddb, table_name))
+    }
+}
+
+#[async_trait]
+impl LobbyRepo for DynamoDbLobbyRepo {
+    async fn get(
+        &self,
+        lobby_code: &LobbyId,
+    ) -> Result<Option<Lobby>, Box<dyn std::error::Error + Sync + Send + 'static>> {
+        let maybe_lobby = self
+            .ddb
+            .get_item(GetItemInput {
+                table_name: self.table_name.clone(),
+                key: {
+                    let mut x = HashMap::new();
+                    x.insert(
+                        "id".to_owned(),
+                        AttributeValue {
+                            s: Some(lobby_code.to_string()),
+                            ..AttributeValue::default()
+                        },
+                    );
+                    x
+                },
+                ..GetItemInput::default()
+            })
+            .await?
+            .item
+            .map(Lobby::from_attrs)
+            .transpose()?;
+
+        log::info!("LobbyRepo::get result: {:?}", &maybe_lobby);
+        Ok(maybe_lobby)
+    }
+
+    async fn create(
+        &self,
+        lobby: &Lobby,
+    ) -> Result<bool, Box<dyn std::error::Error + Sync + Send + 'static>> {
+        let item = lobby.clone().into();
+        let result = self
+            .ddb
+            .put_item(PutItemInput {
+                table_name: self.table_name.clone(),
+                condition_expression: Some("attribute_not_exists(id)".to_string()),
+                item,
+                ..PutItemInput::default()
+            })
+            .await;
+        log::info!("LobbyRepo::create result: {:?}", result);
+        match result {
+            Ok(_) => Ok(true),
+            Err(RusotoError::Service(PutItemError::ConditionalCheckFailed(_))) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn update(
+        &self,
+        lobby_id: &LobbyId,
+        previous_updated_at: &DateTime<Utc>,
+        lobby: &Lobby,
+    ) -> Result<Lobby, Box<dyn std::error::Error + Sync + Send + 'static>> {
+        let item = lobby.clone().into();
+        log::debug!(
+            "LobbyRepo::update updated_at ({}), previous ({})",
+            &lobby.updated_at,
+            previous_updated_at,
+        );
+        let result = self
+            .ddb
+            .put_item(PutItemInput {
+                table_name: self.table_name.clone(),
+                condition_expression: Some("updated_at = :previousUpdatedAt".to_string()),
+                expression_attribute_values: Some(
+                    attr_map! { ":previousUpdatedAt" => previous_updated_at },
+                ),
+                item,
+                ..PutItemInput::default()
+            })
+            .await;
+        log::info!("LobbyRepo::update result: {:?}", result);
+        if let Err(RusotoError::Service(PutItemError::ConditionalCheckFailed(_))) = result {
+            return Err(LobbyServiceError::conflict().into());
+        }
+        result?;
+
+        Ok(self
+            .get(lobby_id)
+            .await?
+            .expect("Could not get the record that was just updated"))
+    }
+
+    /// Kept on the item the `connections` lambda writes for the connection, which it deletes when
+    /// the connection closes.
+    async fn set_connection_lobby(
+        &self,
+        connection_id: &str,
+        lobby_id: &LobbyId,
+    ) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
+        let result = self
+            .ddb
+            .update_item(UpdateItemInput {
+                table_name: self.table_name.clone(),
+                key: attr_map! { "id" => connection_id.to_string() },
+                update_expression: Some("SET lobby_id = :lobbyId".to_string()),
+                expression_attribute_values: Some(attr_map! { ":lobbyId" => lobby_id.clone() }),
+                ..UpdateItemInput::default()
+            })
+            .await?;
+        log::info!("LobbyRepo::set_connection_lobby result: {:?}", result);
+        Ok(())
+    }
+
+    async fn connection_lobby(
+        &self,
+        connection_id: &str,
+    ) -> Result<Option<LobbyId>, Box<dyn std::error::Error + Sync + Send + 'static>> {
+        let lobby_id = self
+            .ddb
+            .get_item(GetItemInput {
+                table_name: self.table_name.clone(),
+                key: attr_map! { "id" => connection_id.to_string() },
+                ..GetItemInput::default()
+            })
+            .await?
+            .item
+            .and_then(|mut attrs| attrs.remove("lobby_id"))
+            .and_then(|value| value.s);
+        Ok(lobby_id)
+    }
+}
+
+/// A `LobbyRepo` which keeps every Lobby in memory.
+///
+/// Nothing is persisted so this is only useful for tests and local development.
+#[derive(Default)]
+pub struct InMemoryLobbyRepo {
+    lobbies: Mutex<HashMap<LobbyId, Lobby>>,
+    connections: Mutex<HashMap<String, LobbyId>>,
+}
+
+impl InMemoryLobbyRepo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl LobbyRepo for InMemoryLobbyRepo {
+    async fn get(
+        &self,
+        lobby_id: &LobbyId,
+    ) -> Result<Option<Lobby>, Box<dyn std::error::Error + Sync + Send + 'static>> {
+        let lobbies = self.lobbies.lock().unwrap();
+        Ok(lobbies.get(lobby_id).cloned())
+    }
+
+    async fn create(
+        &self,
+        lobby: &Lobby,
+    ) -> Result<bool, Box<dyn std::error::Error + Sync + Send + 'static>> {
+        let mut lobbies = self.lobbies.lock().unwrap();
+        if lobbies.contains_key(&lobby.id) {
+            return Ok(false);
+        }
+        lobbies.insert(lobby.id.clone(), lobby.clone());
+        Ok(true)
+    }
+
+    async fn update(
+        &self,
+        lobby_id: &LobbyId,
+        previous_updated_at: &DateTime<Utc>,
+        lobby: &Lobby,
+    ) -> Result<Lobby, Box<dyn std::error::Error + Sync + Send + 'static>> {
+        let mut lobbies = self.lobbies.lock().unwrap();
+        match lobbies.get(lobby_id) {
+            Some(current) if &current.updated_at == previous_updated_at => {
+                lobbies.insert(lobby_id.clone(), lobby.clone());
+                Ok(lobby.clone())
+            }
+            _ => Err(LobbyServiceError::conflict().into()),
+        }
+    }
+
+    async fn set_connection_lobby(
+        &self,
+        connection_id: &str,
+        lobby_id: &LobbyId,
+    ) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
+        let mut connections = self.connections.lock().unwrap();
+        connections.insert(connection_id.to_string(), lobby_id.clone());
+        Ok(())
+    }
+
+    async fn connection_lobby(
+        &self,
+        connection_id: &str,
+    ) -> Result<Option<LobbyId>, Box<dyn std::error::Error + Sync + Send + 'static>> {
+        let connections = self.connections.lock().unwrap();
+        Ok(connections.get(connection_id).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn in_memory_update_rejects_stale_timestamp() {
+        let repo = InMemoryLobbyRepo::new();
+        let created_at = Utc::now();
+        let lobby = Lobby {
+            id: "abcd".to_string(),
+            created_at,
+            updated_at: created_at,
+            players: vec![],
+            host: String::new(),
+            locked: false,
+            auto_start: false,
+            seats: Vec::new(),
+            game: None,
+            chat: vec![],
+        };
+        assert!(repo.create(&lobby).await.expect("failed to create lobby"));
+
+        let mut modified = lobby.clone();
+        modified.updated_at = created_at + chrono::Duration::seconds(1);
+        repo.update(&lobby.id, &created_at, &modified)
+            .await
+            .expect("failed to update lobby");
+
+        // A second writer which read the original Lobby must not clobber the first update.
+        let error = repo
+            .update(&lobby.id, &created_at, &lobby)
+            .await
+            .unwrap_err();
+        let error = error.downcast_ref::<LobbyServiceError>().unwrap();
+        assert_eq!(error.to_response().kind, messages::ErrorKind::TryAgain);
+        let stored = repo.get(&lobby.id).await.unwrap().unwrap();
+        assert_eq!(stored.updated_at, modified.updated_at);
+    }
+}
@@ -0,0 +1,58 @@
+use std::env;
+
+/// How many chat messages a player can send within `CHAT_RATE_WINDOW_SECONDS`.
+pub const CHAT_RATE_LIMIT: usize = 5;
+pub const CHAT_RATE_WINDOW_SECONDS: i64 = 10;
+
+/// Words that chat messages may not contain, matched whole and ignoring case.
+#[derive(Debug, Default, Clone)]
+pub struct WordFilter {
+    words: Vec<String>,
+}
+
+impl WordFilter {
+    pub fn new(words: Vec<String>) -> Self {
+        WordFilter {
+            words: words
+                .iter()
+                .map(|w| w.trim().to_lowercase())
+                .filter(|w| !w.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Reads the comma separated `CHAT_BLOCKED_WORDS`, and filters nothing without it.
+    pub fn from_env() -> Self {
+        match env::var("CHAT_BLOCKED_WORDS") {
+            Ok(words) => Self::new(words.split(',').map(|w| w.to_string()).collect()),
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// The first blocked word in `body`, if there is one.
+    pub fn blocked_word(
+        &self,
+        body: &str,
+    ) -> Option<&String> {
+        body.split(|c: char| !c.is_alphanumeric())
+            .map(|word| word.to_lowercase())
+            .find_map(|word| self.words.iter().find(|&blocked| blocked == &word))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocked_words_match_whole_words_ignoring_case() {
+        let filter = WordFilter::new(vec!["darn".to_string(), " ".to_string()]);
+
+        assert_eq!(
+            filter.blocked_word("Well, DARN!"),
+            Some(&"darn".to_string())
+        );
+        assert_eq!(filter.blocked_word("darning socks"), None);
+        assert_eq!(WordFilter::default().blocked_word("darn"), None);
+    }
+}
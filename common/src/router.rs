@@ -0,0 +1,310 @@
+use chrono::{DateTime, Utc};
+use messages::{ErrorKind, ErrorResponse, Message};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+use crate::handlers;
+use crate::lobby::{LobbyRepo, LobbyServiceError};
+use crate::transport::Transport;
+
+/// Everything a handler needs to act on a message from a connection.
+pub struct Context<'a> {
+    pub repo: &'a dyn LobbyRepo,
+    pub transport: &'a dyn Transport,
+    /// The connection which sent the message.
+    pub connection_id: &'a String,
+    pub now: DateTime<Utc>,
+}
+
+/// Parse the body of a websocket message.
+///
+/// Chat used to be sent as `{"action": "send", ...}` without a `type`, so those bodies are read as
+/// a `LobbyMessage`.
+pub fn parse(body: &str) -> Result<Message, ErrorResponse> {
+    let mut value = serde_json::from_str::<Value>(body).map_err(malformed_json)?;
+    if value.get("type").is_none() && value.get("action") == Some(&json!("send")) {
+        value["type"] = json!("lobby_message");
+    }
+
+    serde_path_to_error::deserialize::<_, Message>(value.clone()).map_err(|e| {
+        let path = e.path().to_string();
+        let message = e.into_inner().to_string();
+        if path == "type" {
+            return ErrorResponse {
+                kind: ErrorKind::UnknownAction,
+                message,
+                path: Some(path),
+            };
+        }
+        // Fields inside a message are reported against the root, since serde reads the rest of
+        // the message before it knows which `type` it is, so look for them again in that type.
+        let path = value
+            .get("type")
+            .and_then(Value::as_str)
+            .and_then(|kind| field_path(kind, value.clone()))
+            .filter(|path| path != ".");
+        ErrorResponse {
+            kind: ErrorKind::InvalidMessage,
+            message,
+            path,
+        }
+    })
+}
+
+/// Where deserializing `value` as the message of type `kind` fails, for the messages clients send.
+fn field_path(
+    kind: &str,
+    value: Value,
+) -> Option<String> {
+    fn path_of<T: DeserializeOwned>(value: Value) -> Option<String> {
+        serde_path_to_error::deserialize::<_, T>(value)
+            .err()
+            .map(|e| e.path().to_string())
+    }
+    match kind {
+        "lobby_action_create" => path_of::<messages::LobbyActionCreate>(value),
+        "lobby_action_join" => path_of::<messages::LobbyActionJoin>(value),
+        "lobby_action_mute" => path_of::<messages::LobbyActionMute>(value),
+        "lobby_action_kick" => path_of::<messages::LobbyActionKick>(value),
+        "lobby_action_transfer_host" => path_of::<messages::LobbyActionTransferHost>(value),
+        "lobby_action_lock" => path_of::<messages::LobbyActionLock>(value),
+        "lobby_action_take_seat" => path_of::<messages::LobbyActionTakeSeat>(value),
+        "lobby_action_swap_seats" => path_of::<messages::LobbyActionSwapSeats>(value),
+        "lobby_action_ready" => path_of::<messages::LobbyActionReady>(value),
+        "lobby_action_auto_start" => path_of::<messages::LobbyActionAutoStart>(value),
+        "lobby_action_leave" => path_of::<messages::LobbyActionLeave>(value),
+        "resume" => path_of::<messages::Resume>(value),
+        "lobby_message" => path_of::<messages::LobbyMessage>(value),
+        "lobby_message_history" => path_of::<messages::LobbyMessageHistory>(value),
+        "lobby_reaction" => path_of::<messages::LobbyReaction>(value),
+        "game_action_start" => path_of::<messages::GameActionStart>(value),
+        "game_action_play" => path_of::<messages::GameActionPlay>(value),
+        "game_action_pass" => path_of::<messages::GameActionPass>(value),
+        _ => None,
+    }
+}
+
+/// Explain why a body could not be routed, as API Gateway does when it falls back to `$default`.
+pub fn unroutable(body: &str) -> ErrorResponse {
+    let value = match serde_json::from_str::<Value>(body) {
+        Ok(value) => value,
+        Err(e) => return malformed_json(e),
+    };
+    let message = match value.get("action") {
+        Some(Value::String(action)) => format!("unknown action `{}`", action),
+        Some(_) => "`action` must be a string".to_string(),
+        None => "missing field `action`".to_string(),
+    };
+    ErrorResponse {
+        kind: ErrorKind::UnknownAction,
+        message,
+        path: Some("action".to_string()),
+    }
+}
+
+fn malformed_json(e: serde_json::Error) -> ErrorResponse {
+    ErrorResponse {
+        kind: ErrorKind::MalformedJson,
+        message: e.to_string(),
+        path: None,
+    }
+}
+
+/// Parse the body of a websocket message and dispatch it.
+pub async fn route(
+    ctx: &Context<'_>,
+    body: &str,
+) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
+    let result = match parse(body) {
+        Ok(message) => dispatch(ctx, message).await,
+        Err(error) => return reject(ctx.transport, ctx.connection_id, body, error).await,
+    };
+    match result {
+        // Mistakes by the player are theirs to fix, so tell them what went wrong.
+        Err(e) => match e.downcast_ref::<LobbyServiceError>() {
+            Some(error) => {
+                reject(ctx.transport, ctx.connection_id, body, error.to_response()).await
+            }
+            None => Err(e),
+        },
+        Ok(()) => Ok(()),
+    }
+}
+
+/// Let the lobby a connection was in know that it has closed, like the `$disconnect` route does.
+pub async fn disconnect(
+    ctx: &Context<'_>
+) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
+    handlers::lobby::disconnect(ctx).await
+}
+
+/// Tell a connection why the message it sent could not be handled.
+pub async fn reject(
+    transport: &dyn Transport,
+    connection_id: &String,
+    body: &str,
+    error: ErrorResponse,
+) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
+    log::warn!(
+        "{}",
+        json!({
+            "rejected": body,
+            "connection_id": connection_id,
+            "kind": error.kind,
+            "message": error.message,
+            "path": error.path,
+        })
+    );
+    transport
+        .post_to_connection(connection_id, Message::Error(error))
+        .await
+}
+
+/// Call the handler for a message.
+pub async fn dispatch(
+    ctx: &Context<'_>,
+    message: Message,
+) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
+    log::info!("message {:?}", message);
+    match message {
+        Message::LobbyActionCreate(m) => handlers::lobby::create(ctx, m).await,
+        Message::LobbyActionJoin(m) => handlers::lobby::join(ctx, m).await,
+        Message::LobbyActionMute(m) => handlers::lobby::mute(ctx, m).await,
+        Message::LobbyActionKick(m) => handlers::lobby::kick(ctx, &m.lobby_code, &m.name).await,
+        Message::LobbyActionTransferHost(m) => handlers::lobby::transfer_host(ctx, m).await,
+        Message::LobbyActionLock(m) => handlers::lobby::lock(ctx, m).await,
+        Message::LobbyActionTakeSeat(m) => handlers::lobby::take_seat(ctx, m).await,
+        Message::LobbyActionSwapSeats(m) => handlers::lobby::swap_seats(ctx, m).await,
+        Message::LobbyActionReady(m) => handlers::lobby::ready(ctx, &m.lobby_code, m.ready).await,
+        Message::LobbyActionAutoStart(m) => handlers::lobby::auto_start(ctx, m).await,
+        Message::LobbyActionLeave(m) => handlers::lobby::leave(ctx, m).await,
+        Message::Resume(m) => handlers::lobby::resume(ctx, m).await,
+        Message::LobbyMessage(m) => handlers::chat::send(ctx, m).await,
+        Message::LobbyMessageHistory(m) => handlers::chat::history(ctx, m).await,
+        Message::LobbyReaction(m) => handlers::chat::react(ctx, m).await,
+        Message::GameActionStart(m) => handlers::game::start(ctx, m).await,
+        Message::GameActionPlay(m) => handlers::game::play(ctx, m).await,
+        Message::GameActionPass(m) => handlers::game::pass(ctx, m).await,
+        // Responses are only ever sent from the server to clients.
+        Message::LobbyActionCreateResponse(_)
+        | Message::LobbyActionJoinResponse(_)
+        | Message::LobbyUpdate(_)
+        | Message::ResumeResponse(_)
+        | Message::LobbyMessageResponse(_)
+        | Message::SystemMessage(_)
+        | Message::LobbyReactionResponse(_)
+        | Message::LobbyMessageHistoryResponse(_)
+        | Message::GameUpdate(_)
+        | Message::ScoreUpdate(_)
+        | Message::Error(_) => {
+            log::info!("Invalid action");
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_typed_message() {
+        let message =
+            parse(r#"{"action": "hearts", "type": "lobby_action_create", "name": "Host"}"#)
+                .expect("failed to parse message");
+        assert_eq!(
+            message,
+            Message::LobbyActionCreate(messages::LobbyActionCreate {
+                name: "Host".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_json() {
+        let error = parse("{\"type\": ").unwrap_err();
+        assert_eq!(error.kind, ErrorKind::MalformedJson);
+    }
+
+    #[test]
+    fn rejects_unknown_type() {
+        let error = parse(r#"{"action": "hearts", "type": "lobby_action_dance"}"#).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::UnknownAction);
+        assert_eq!(error.path, Some("type".to_string()));
+    }
+
+    #[test]
+    fn rejects_missing_fields() {
+        let error = parse(r#"{"action": "hearts", "type": "lobby_action_join", "name": "Amigo"}"#)
+            .unwrap_err();
+        assert_eq!(error.kind, ErrorKind::InvalidMessage);
+        assert_eq!(error.message, "missing field `lobby_code`");
+    }
+
+    #[test]
+    fn rejects_invalid_fields_inside_a_known_type() {
+        let error = parse(
+            r#"{"action": "hearts", "type": "game_action_play", "lobby_code": "hljk",
+                "card": {"suit": "stars", "rank": "queen"}}"#,
+        )
+        .unwrap_err();
+        assert_eq!(error.kind, ErrorKind::InvalidMessage);
+        assert_eq!(error.path, Some("card.suit".to_string()));
+    }
+
+    #[test]
+    fn points_at_invalid_fields_of_every_client_message() {
+        // Only the server sends these, so they are left out of `field_path`.
+        let server_only = [
+            "lobby_action_create_response",
+            "lobby_action_join_response",
+            "lobby_update",
+            "resume_response",
+            "lobby_message_response",
+            "system_message",
+            "lobby_reaction_response",
+            "lobby_message_history_response",
+            "game_update",
+            "score_update",
+            "error",
+        ];
+        let unknown = parse(r#"{"type": "?"}"#).unwrap_err().message;
+        let (_, expected) = unknown.split_at(unknown.find("expected one of").unwrap());
+        let kinds: Vec<&str> = expected.split('`').skip(1).step_by(2).collect();
+        assert!(kinds.len() > server_only.len());
+
+        for kind in kinds.into_iter().filter(|k| !server_only.contains(k)) {
+            let body = json!({"type": kind, "lobby_code": 1, "name": 1}).to_string();
+            let error = parse(&body).unwrap_err();
+            assert_eq!(error.kind, ErrorKind::InvalidMessage, "{}", kind);
+            let expected = if kind == "lobby_action_create" {
+                "name"
+            } else {
+                "lobby_code"
+            };
+            assert_eq!(error.path, Some(expected.to_string()), "{}", kind);
+        }
+    }
+
+    #[test]
+    fn explains_unknown_action() {
+        let error = unroutable(r#"{"action": "dance"}"#);
+        assert_eq!(error.kind, ErrorKind::UnknownAction);
+        assert_eq!(error.message, "unknown action `dance`");
+    }
+
+    #[test]
+    fn parses_untyped_chat_message() {
+        let message = parse(r#"{"action": "send", "lobby_code": "hljk", "body": "howdy"}"#)
+            .expect("failed to parse message");
+        assert_eq!(
+            message,
+            Message::LobbyMessage(messages::LobbyMessage {
+                lobby_code: "hljk".to_string(),
+                body: "howdy".to_string(),
+                client_id: None,
+                to: None,
+            })
+        );
+    }
+}
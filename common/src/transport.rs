@@ -0,0 +1,122 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use futures::channel::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use serde_json::json;
+
+/// Delivers messages to the websocket connections of players.
+#[async_trait]
+pub trait Transport: Send + Sync {
+    async fn post_to_connection(
+        &self,
+        connection_id: &str,
+        message: messages::Message,
+    ) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>>;
+}
+
+/// A `Transport` for sockets which are held by this process, like the ones in `local_server`.
+#[derive(Clone, Default)]
+pub struct LocalTransport {
+    connections: Arc<Mutex<HashMap<String, UnboundedSender<String>>>>,
+}
+
+impl LocalTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a connection and return the stream of serialized messages posted to it.
+    pub fn connect(
+        &self,
+        connection_id: &str,
+    ) -> UnboundedReceiver<String> {
+        let (tx, rx) = mpsc::unbounded();
+        self.connections
+            .lock()
+            .unwrap()
+            .insert(connection_id.to_string(), tx);
+        rx
+    }
+
+    pub fn disconnect(
+        &self,
+        connection_id: &str,
+    ) {
+        self.connections.lock().unwrap().remove(connection_id);
+    }
+}
+
+#[async_trait]
+impl Transport for LocalTransport {
+    async fn post_to_connection(
+        &self,
+        connection_id: &str,
+        message: messages::Message,
+    ) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
+        let data = json!(message).to_string();
+        let connections = self.connections.lock().unwrap();
+        match connections.get(connection_id) {
+            Some(tx) => Ok(tx.unbounded_send(data)?),
+            None => Err(format!("Connection {} is gone", connection_id).into()),
+        }
+    }
+}
+
+/// A `Transport` which records every message instead of delivering it.
+///
+/// Tests use this to assert on the exact messages each connection received.
+#[derive(Default)]
+pub struct RecordingTransport {
+    posted: Mutex<Vec<(String, messages::Message)>>,
+    closed: Mutex<HashSet<String>>,
+}
+
+impl RecordingTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every message posted to `connection_id`, oldest first.
+    pub fn messages_for(
+        &self,
+        connection_id: &str,
+    ) -> Vec<messages::Message> {
+        self.posted
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(id, _)| id == connection_id)
+            .map(|(_, message)| message.clone())
+            .collect()
+    }
+
+    /// Fail every later post to `connection_id`, like a socket that has gone away.
+    pub fn close(
+        &self,
+        connection_id: &str,
+    ) {
+        self.closed
+            .lock()
+            .unwrap()
+            .insert(connection_id.to_string());
+    }
+}
+
+#[async_trait]
+impl Transport for RecordingTransport {
+    async fn post_to_connection(
+        &self,
+        connection_id: &str,
+        message: messages::Message,
+    ) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
+        if self.closed.lock().unwrap().contains(connection_id) {
+            return Err(format!("Connection {} is gone", connection_id).into());
+        }
+        self.posted
+            .lock()
+            .unwrap()
+            .push((connection_id.to_string(), message));
+        Ok(())
+    }
+}
@@ -1,37 +1,47 @@
+use async_trait::async_trait;
 use rusoto_apigatewaymanagementapi::{
-    ApiGatewayManagementApi, ApiGatewayManagementApiClient, PostToConnectionError,
-    PostToConnectionRequest,
+    ApiGatewayManagementApi, ApiGatewayManagementApiClient, PostToConnectionRequest,
 };
 use rusoto_core::Region;
 use serde_json::json;
 
+use crate::transport::Transport;
+
+/// A `Transport` which posts to connections through the API Gateway management API.
 pub struct WebSocketClient {
     client: ApiGatewayManagementApiClient,
 }
 
 impl WebSocketClient {
+    /// Create a client for the API Gateway stage at `endpoint`.
+    ///
+    /// The region used for signing comes from the environment, which is set for us in Lambda.
     pub fn new(endpoint: &String) -> Self {
         let client = ApiGatewayManagementApiClient::new(Region::Custom {
-            name: Region::CaCentral1.name().into(),
+            name: Region::default().name().into(),
             endpoint: endpoint.to_string(),
         });
-        return Self { client };
+        Self { client }
     }
+}
 
-    pub async fn post_to_connection(
-        self,
-        connection_id: &String,
+#[async_trait]
+impl Transport for WebSocketClient {
+    async fn post_to_connection(
+        &self,
+        connection_id: &str,
         message: messages::Message,
-    ) -> Result<(), rusoto_core::RusotoError<PostToConnectionError>> {
+    ) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
         self.client
             .post_to_connection(PostToConnectionRequest {
-                connection_id: connection_id.clone(),
+                connection_id: connection_id.to_string(),
                 data: serde_json::to_vec(&json!(message))
                     .unwrap_or_default()
                     .into(),
             })
-            .await
+            .await?;
         // TODO elsewhere we have used `PostToConnectionError::Gone(_)` to delete connection
         // objects. We should likely be doing something like that.
+        Ok(())
     }
 }
@@ -1,3 +1,4 @@
+use chrono::Utc;
 use dynomite::{
     dynamodb::{DeleteItemInput, DynamoDb, DynamoDbClient, PutItemInput},
     Item,
@@ -7,6 +8,11 @@ use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::env;
 
+use common::event;
+use common::lobby::DynamoDbLobbyRepo;
+use common::router;
+use common::websocket_client::WebSocketClient;
+
 type Error = Box<dyn std::error::Error + Sync + Send + 'static>;
 
 thread_local!(
@@ -29,7 +35,9 @@ struct Event {
 #[serde(rename_all = "camelCase")]
 struct RequestContext {
     event_type: EventType,
-    connection_id: String,
+    /// The fields every websocket route is given, like the connection.
+    #[serde(flatten)]
+    websocket: event::RequestContext,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -52,7 +60,7 @@ async fn connector(
 ) -> Result<Value, Error> {
     let table_name = env::var("tableName")?;
     let connection = Connection {
-        id: event.request_context.connection_id,
+        id: event.request_context.websocket.connection_id.clone(),
     };
     match event.request_context.event_type {
         EventType::Connect => {
@@ -76,6 +84,10 @@ async fn connector(
         }
         EventType::Disconnect => {
             log::info!("disconnecting {}", connection.id);
+            // Hand the lobby on before forgetting which lobby the connection was in.
+            if let Err(err) = leave_lobby(&event.request_context.websocket).await {
+                log::error!("failed to update the lobby of {}: {:?}", connection.id, err);
+            }
             DDB.with(|ddb| {
                 let ddb = ddb.clone();
                 async move {
@@ -100,6 +112,19 @@ async fn connector(
     }))
 }
 
+/// Mark the player on a closed connection as away, handing the host on if it was theirs.
+async fn leave_lobby(request_context: &event::RequestContext) -> Result<(), Error> {
+    let repo = DynamoDbLobbyRepo::from_env(DynamoDbClient::new(Default::default()))?;
+    let transport = WebSocketClient::new(&request_context.endpoint());
+    let ctx = router::Context {
+        repo: &repo,
+        transport: &transport,
+        connection_id: &request_context.connection_id,
+        now: Utc::now(),
+    };
+    router::disconnect(&ctx).await
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
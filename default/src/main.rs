@@ -1,20 +1,45 @@
 use lambda::{handler_fn, Context};
 use serde_json::{json, Value};
 
+use common::event::Event;
+use common::router;
+use common::websocket_client::WebSocketClient;
+
 type Error = Box<dyn std::error::Error + Sync + Send + 'static>;
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
+    env_logger::init();
     lambda::run(handler_fn(default)).await?;
     Ok(())
 }
 
+/// Handles messages whose `action` doesn't match any other route by telling the sender why.
 async fn default(
     event: Value,
     _context: Context,
 ) -> Result<Value, Error> {
-    println!("default {:#?}", event);
-    // todo: something more appropriate
+    match serde_json::from_value::<Event>(event.clone()) {
+        Ok(event) => {
+            let transport = WebSocketClient::new(&event.request_context.endpoint());
+            let error = router::unroutable(&event.body);
+            router::reject(
+                &transport,
+                &event.request_context.connection_id,
+                &event.body,
+                error,
+            )
+            .await?;
+        }
+        Err(e) => {
+            // Without a connection there is nobody to tell.
+            log::error!(
+                "{}",
+                json!({ "unreadable_event": event, "error": e.to_string() })
+            );
+        }
+    }
+
     Ok(json!({
         "statusCode": 400
     }))
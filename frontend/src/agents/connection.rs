@@ -0,0 +1,227 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use anyhow::Error;
+use serde_json::json;
+use yew::agent::{Agent, AgentLink, Context, HandlerId};
+use yew::format::Json;
+use yew::services::timeout::{TimeoutService, TimeoutTask};
+use yew::services::websocket::{WebSocketService, WebSocketStatus, WebSocketTask};
+
+/// Where `local_server` listens by default.
+const DEFAULT_ENDPOINT: &str = "ws://127.0.0.1:9001";
+
+/// How long to wait before the first attempt to reconnect, doubling after every failure.
+const FIRST_RETRY: Duration = Duration::from_millis(500);
+const MAX_RETRY: Duration = Duration::from_secs(30);
+
+/// The websocket URL to connect to.
+///
+/// A `<meta name="websocket-url" content="...">` tag in the page wins, so one build can be pointed
+/// at any stage. Otherwise `WEBSOCKET_URL` is read at build time, falling back to a local server.
+fn endpoint() -> String {
+    let from_page = yew::utils::document()
+        .query_selector("meta[name='websocket-url']")
+        .ok()
+        .flatten()
+        .and_then(|meta| meta.get_attribute("content"))
+        .filter(|url| !url.is_empty());
+    from_page
+        .or_else(|| option_env!("WEBSOCKET_URL").map(str::to_owned))
+        .unwrap_or_else(|| DEFAULT_ENDPOINT.to_owned())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Status {
+    Connecting,
+    Connected,
+    /// The connection was lost and will be retried, this many times so far.
+    Reconnecting(u32),
+    Disconnected,
+}
+
+pub enum Request {
+    Connect,
+    Disconnect,
+    Send(messages::Message),
+}
+
+#[derive(Clone, Debug)]
+pub enum Response {
+    Status(Status),
+    /// A message was sent to the server, for anyone who wants to show it before it is answered.
+    Sent(messages::Message),
+    Received(messages::Message),
+}
+
+pub enum Msg {
+    Received(Result<messages::Message, Error>),
+    Opened,
+    Lost,
+    Retry,
+}
+
+/// The one websocket connection to the server, shared by every component that bridges to it.
+///
+/// Everything received is passed on to every bridge, so components pick out the messages they
+/// care about.
+pub struct Connection {
+    link: AgentLink<Self>,
+    endpoint: String,
+    ws: Option<WebSocketTask>,
+    status: Status,
+    subscribers: HashSet<HandlerId>,
+    /// Whether someone asked to be connected, so a lost connection should be retried.
+    wanted: bool,
+    attempts: u32,
+    retry: Option<TimeoutTask>,
+}
+
+impl Connection {
+    fn broadcast(&self, response: Response) {
+        for id in self.subscribers.iter() {
+            self.link.respond(*id, response.clone());
+        }
+    }
+
+    fn set_status(&mut self, status: Status) {
+        self.status = status;
+        self.broadcast(Response::Status(status));
+    }
+
+    fn connect(&mut self) {
+        if self.ws.is_some() {
+            return;
+        }
+        let callback = self.link.callback(|Json(data)| Msg::Received(data));
+        let notification = self.link.callback(|status| match status {
+            WebSocketStatus::Opened => Msg::Opened,
+            WebSocketStatus::Closed | WebSocketStatus::Error => Msg::Lost,
+        });
+        match WebSocketService::connect(&self.endpoint, callback, notification) {
+            Ok(task) => {
+                self.ws = Some(task);
+                if self.attempts == 0 {
+                    self.set_status(Status::Connecting);
+                }
+            }
+            Err(e) => {
+                log::error!("Could not connect to {}: {}", self.endpoint, e);
+                self.retry_later();
+            }
+        }
+    }
+
+    fn retry_later(&mut self) {
+        if !self.wanted || self.retry.is_some() {
+            return;
+        }
+        let delay = FIRST_RETRY
+            .checked_mul(1u32 << self.attempts.min(16))
+            .map_or(MAX_RETRY, |delay| delay.min(MAX_RETRY));
+        self.attempts += 1;
+        log::info!("Reconnecting in {:?}", delay);
+        self.retry = Some(TimeoutService::spawn(
+            delay,
+            self.link.callback(|_| Msg::Retry),
+        ));
+        self.set_status(Status::Reconnecting(self.attempts));
+    }
+
+    fn send(&mut self, message: messages::Message) {
+        let ws = match self.ws.as_mut() {
+            Some(ws) => ws,
+            None => {
+                log::error!("Not connected, dropping {:?}", message);
+                return;
+            }
+        };
+        let mut data = match serde_json::to_value(&message) {
+            Ok(data) => data,
+            Err(e) => {
+                log::error!("Could not serialize {:?}: {}", message, e);
+                return;
+            }
+        };
+        // API Gateway routes on `action`, which isn't part of the message itself.
+        data["action"] = json!("hearts");
+        ws.send(Json(&data));
+        self.broadcast(Response::Sent(message));
+    }
+}
+
+impl Agent for Connection {
+    type Reach = Context<Self>;
+    type Message = Msg;
+    type Input = Request;
+    type Output = Response;
+
+    fn create(link: AgentLink<Self>) -> Self {
+        let endpoint = endpoint();
+        log::info!("Using websocket {}", endpoint);
+        Self {
+            link,
+            endpoint,
+            ws: None,
+            status: Status::Disconnected,
+            subscribers: HashSet::new(),
+            wanted: false,
+            attempts: 0,
+            retry: None,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) {
+        match msg {
+            Msg::Received(Ok(message)) => {
+                log::info!("Received message from WebSocket, {:?}", &message);
+                self.broadcast(Response::Received(message));
+            }
+            Msg::Received(Err(e)) => log::error!("Received bad message: {}", e),
+            Msg::Opened => {
+                self.attempts = 0;
+                self.set_status(Status::Connected);
+            }
+            Msg::Lost => {
+                log::info!("WebSocket connection lost.");
+                self.ws = None;
+                if self.wanted {
+                    self.retry_later();
+                } else {
+                    self.set_status(Status::Disconnected);
+                }
+            }
+            Msg::Retry => {
+                self.retry = None;
+                self.connect();
+            }
+        }
+    }
+
+    fn handle_input(&mut self, msg: Self::Input, _id: HandlerId) {
+        match msg {
+            Request::Connect => {
+                self.wanted = true;
+                self.connect();
+            }
+            Request::Disconnect => {
+                log::info!("Disconnecting from WebSocket");
+                self.wanted = false;
+                self.attempts = 0;
+                self.retry = None;
+                self.ws = None;
+                self.set_status(Status::Disconnected);
+            }
+            Request::Send(message) => self.send(message),
+        }
+    }
+
+    fn connected(&mut self, id: HandlerId) {
+        self.subscribers.insert(id);
+        self.link.respond(id, Response::Status(self.status));
+    }
+
+    fn disconnected(&mut self, id: HandlerId) {
+        self.subscribers.remove(&id);
+    }
+}
@@ -0,0 +1,164 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+static NEXT_CLIENT_ID: AtomicU32 = AtomicU32::new(1);
+
+/// An id for a message we are about to send, unique for as long as the page is open.
+pub fn next_client_id() -> String {
+    format!("pending-{}", NEXT_CLIENT_ID.fetch_add(1, Ordering::Relaxed))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChatLine {
+    /// Sent by us and not yet echoed back by the server.
+    Pending {
+        client_id: String,
+        body: String,
+        to: Option<messages::Recipient>,
+    },
+    /// Sent by us but never echoed back, so it may need sending again.
+    Failed {
+        client_id: String,
+        body: String,
+        /// Who it was for, so sending it again doesn't go to whoever is picked now.
+        to: Option<messages::Recipient>,
+    },
+    Confirmed(messages::LobbyMessageResponse),
+    /// Said by the server rather than a player.
+    System(messages::SystemMessage),
+}
+
+impl ChatLine {
+    fn client_id(&self) -> Option<&String> {
+        match self {
+            ChatLine::Pending { client_id, .. } | ChatLine::Failed { client_id, .. } => {
+                Some(client_id)
+            }
+            ChatLine::Confirmed(m) => m.client_id.as_ref(),
+            ChatLine::System(_) => None,
+        }
+    }
+
+    /// The id the server gave the message, once it has one.
+    fn server_id(&self) -> Option<&String> {
+        match self {
+            ChatLine::Confirmed(m) => Some(&m.id),
+            ChatLine::System(m) => Some(&m.id),
+            _ => None,
+        }
+    }
+}
+
+/// The chat in the current lobby, including our own messages which are still on their way.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Chat {
+    pub lines: Vec<ChatLine>,
+    /// Whether the server has older messages than the ones we have.
+    pub more_history: bool,
+}
+
+impl Chat {
+    fn position(&self, client_id: &String) -> Option<usize> {
+        self.lines
+            .iter()
+            .position(|line| line.client_id() == Some(client_id))
+    }
+
+    /// Show a message we sent, or show it as pending again if it is being resent.
+    pub fn sent(&mut self, client_id: String, body: String, to: Option<messages::Recipient>) {
+        match self.position(&client_id) {
+            Some(i) => {
+                if let ChatLine::Failed { .. } = self.lines[i] {
+                    self.lines[i] = ChatLine::Pending {
+                        client_id,
+                        body,
+                        to,
+                    };
+                }
+            }
+            None => self.lines.push(ChatLine::Pending {
+                client_id,
+                body,
+                to,
+            }),
+        }
+    }
+
+    /// Add a message from the server, replacing our pending copy if it is one of ours.
+    pub fn received(&mut self, message: messages::LobbyMessageResponse) {
+        let already_confirmed = self.lines.iter().any(|line| match line {
+            ChatLine::Confirmed(m) => m.id == message.id,
+            _ => false,
+        });
+        if already_confirmed {
+            return;
+        }
+        let ours = message.client_id.as_ref().and_then(|id| self.position(id));
+        match ours {
+            Some(i) => self.lines[i] = ChatLine::Confirmed(message),
+            None => self.lines.push(ChatLine::Confirmed(message)),
+        }
+    }
+
+    pub fn system(&mut self, message: messages::SystemMessage) {
+        self.lines.push(ChatLine::System(message));
+    }
+
+    /// Add messages the server kept in the order they were sent, skipping any we already have.
+    ///
+    /// Older pages go before the lines we have, while the latest page after a resume can go
+    /// after them.
+    pub fn history(&mut self, messages: Vec<messages::LobbyMessageResponse>, more: bool) {
+        for message in messages {
+            if self
+                .lines
+                .iter()
+                .any(|line| line.server_id() == Some(&message.id))
+            {
+                continue;
+            }
+            let at = self
+                .lines
+                .iter()
+                .position(|line| match line {
+                    ChatLine::Confirmed(m) => m.sent_at > message.sent_at,
+                    ChatLine::System(m) => m.sent_at > message.sent_at,
+                    _ => false,
+                })
+                .unwrap_or_else(|| self.lines.len());
+            let line = if message.system {
+                ChatLine::System(messages::SystemMessage {
+                    id: message.id,
+                    sent_at: message.sent_at,
+                    body: message.body,
+                })
+            } else {
+                ChatLine::Confirmed(message)
+            };
+            self.lines.insert(at, line);
+        }
+        self.more_history = more;
+    }
+
+    /// The id of the oldest message we have from the server, to ask for the ones before it.
+    pub fn oldest_id(&self) -> Option<&String> {
+        self.lines.iter().find_map(ChatLine::server_id)
+    }
+
+    /// Give up waiting for the server to confirm a message.
+    pub fn failed(&mut self, client_id: &String) {
+        if let Some(i) = self.position(client_id) {
+            if let ChatLine::Pending {
+                client_id,
+                body,
+                to,
+            } = &self.lines[i]
+            {
+                self.lines[i] = ChatLine::Failed {
+                    client_id: client_id.clone(),
+                    body: body.clone(),
+                    to: to.clone(),
+                };
+            }
+        }
+    }
+}
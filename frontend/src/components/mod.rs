@@ -1 +1,3 @@
 pub mod loading_spinner;
+pub mod playing_card;
+pub mod scoreboard;
@@ -0,0 +1,66 @@
+use game::Card;
+use yew::prelude::*;
+use yewtil::NeqAssign;
+
+#[derive(Clone, Debug, PartialEq, Properties)]
+pub struct Props {
+    pub card: Card,
+    #[prop_or_default]
+    pub disabled: bool,
+    /// Draws attention to the card, like when it has been picked or was just received.
+    #[prop_or_default]
+    pub highlighted: bool,
+    #[prop_or_default]
+    pub on_click: Callback<Card>,
+}
+
+pub struct PlayingCard {
+    link: ComponentLink<Self>,
+    props: Props,
+}
+
+pub enum Msg {
+    Clicked,
+}
+
+impl Component for PlayingCard {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        Self { link, props }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Msg::Clicked => self.props.on_click.emit(self.props.card),
+        }
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props.neq_assign(props)
+    }
+
+    fn view(&self) -> Html {
+        let colour = if self.props.card.suit.is_red() {
+            "text-red-600"
+        } else {
+            "text-black"
+        };
+        let highlight = if self.props.highlighted {
+            "ring-4 ring-yellow-400 -translate-y-2 transform"
+        } else {
+            ""
+        };
+        html! {
+            <button
+                class=format!("w-12 h-16 m-1 bg-white rounded-md shadow-md disabled:opacity-50 {} {}", colour, highlight)
+                disabled=self.props.disabled
+                onclick=self.link.callback(|_| Msg::Clicked)
+            >
+                { self.props.card.to_string() }
+            </button>
+        }
+    }
+}
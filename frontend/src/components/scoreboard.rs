@@ -0,0 +1,140 @@
+use yew::prelude::*;
+use yewtil::NeqAssign;
+
+#[derive(Clone, Debug, PartialEq, Properties)]
+pub struct Props {
+    pub scores: messages::ScoreUpdate,
+}
+
+/// Every hand's scores so far, with the penalty cards behind them.
+pub struct Scoreboard {
+    link: ComponentLink<Self>,
+    props: Props,
+
+    expanded_hand: Option<u32>,
+}
+
+pub enum Msg {
+    ToggleHand(u32),
+}
+
+impl Scoreboard {
+    fn view_hand(&self, hand: &messages::HandResult) -> Html {
+        let hand_number = hand.hand_number;
+        let expanded = self.expanded_hand == Some(hand_number);
+        html! {
+            <>
+                <tr
+                    class="cursor-pointer hover:bg-green-100"
+                    onclick=self.link.callback(move |_| Msg::ToggleHand(hand_number))
+                >
+                    <td class="px-2">{ hand_number }</td>
+                    {
+                        for hand.points.iter().enumerate().map(|(seat, points)| {
+                            let moon = if hand.moon == Some(seat) { " 🌙" } else { "" };
+                            html! { <td class="px-2 text-right">{ format!("{}{}", points, moon) }</td> }
+                        })
+                    }
+                </tr>
+                { if expanded { self.view_penalty_cards(hand) } else { html! {} } }
+            </>
+        }
+    }
+
+    fn view_penalty_cards(&self, hand: &messages::HandResult) -> Html {
+        html! {
+            <tr class="text-sm bg-green-50">
+                <td class="px-2"></td>
+                {
+                    for hand.taken.iter().map(|cards| {
+                        let cards = cards
+                            .iter()
+                            .map(|c| c.to_string())
+                            .collect::<Vec<String>>()
+                            .join(" ");
+                        html! { <td class="px-2 text-right">{ cards }</td> }
+                    })
+                }
+            </tr>
+        }
+    }
+
+    fn view_moons(&self) -> Html {
+        let scores = &self.props.scores;
+        let moons = scores
+            .hands
+            .iter()
+            .filter_map(|hand| {
+                let seat = hand.moon?;
+                let name = scores.players.get(seat)?;
+                Some(format!(
+                    "{} shot the moon in hand {}",
+                    name, hand.hand_number
+                ))
+            })
+            .collect::<Vec<String>>();
+        html! {
+            <>
+                { for moons.into_iter().map(|moon| html! { <p class="text-sm">{ moon }</p> }) }
+            </>
+        }
+    }
+}
+
+impl Component for Scoreboard {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        Self {
+            link,
+            props,
+            expanded_hand: None,
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Msg::ToggleHand(hand_number) => {
+                if self.expanded_hand == Some(hand_number) {
+                    self.expanded_hand = None;
+                } else {
+                    self.expanded_hand = Some(hand_number);
+                }
+            }
+        }
+        true
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props.neq_assign(props)
+    }
+
+    fn view(&self) -> Html {
+        let scores = &self.props.scores;
+        html! {
+            <div class="p-2 bg-white shadow-lg">
+                <h2 class="font-bold">
+                    { format!("Scores · lobby {}", scores.lobby_code) }
+                    { if scores.finished { " · Final" } else { "" } }
+                </h2>
+                <table class="table-auto">
+                    <thead>
+                        <tr>
+                            <th class="px-2">{ "Hand" }</th>
+                            { for scores.players.iter().map(|name| html! { <th class="px-2">{ name }</th> }) }
+                        </tr>
+                    </thead>
+                    <tbody>
+                        { for scores.hands.iter().map(|hand| self.view_hand(hand)) }
+                        <tr class="font-bold border-t">
+                            <td class="px-2">{ "Total" }</td>
+                            { for scores.totals.iter().map(|total| html! { <td class="px-2 text-right">{ total }</td> }) }
+                        </tr>
+                    </tbody>
+                </table>
+                { self.view_moons() }
+            </div>
+        }
+    }
+}
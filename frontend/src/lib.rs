@@ -1,83 +1,227 @@
 #![recursion_limit = "512"]
 
+use std::collections::HashMap;
+use std::time::Duration;
+
 use wasm_bindgen::prelude::*;
 
-use anyhow::Error;
-use serde_json::Value;
-use yew::format::Json;
+use yew::agent::{Bridge, Bridged};
 use yew::prelude::*;
-use yew::services::websocket::{WebSocketService, WebSocketStatus, WebSocketTask};
+use yew::services::timeout::{TimeoutService, TimeoutTask};
+use yew_router::agent::{RouteAgentDispatcher, RouteRequest};
 use yew_router::{route::Route, switch::Permissive};
 
+mod chat;
+use chat::Chat;
+
+mod agents;
+use agents::connection::{self, Connection, Status};
+
 mod components;
+use components::scoreboard::Scoreboard;
+
+mod storage;
+use storage::{Saved, Session};
 
 mod scenes;
 use scenes::{lobby::Lobby, out_of_lobby::OutOfLobby, page_not_found::PageNotFound};
 mod switch;
 use switch::{AppAnchor, AppRoute, AppRouter, PublicUrlSwitch};
 
-struct Requests {
-    joining_lobby: bool,
-    creating_lobby: bool,
-}
+/// How long to wait for the server to echo a chat message before offering to send it again.
+const CHAT_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long a reaction stays on screen.
+const REACTION_TIMEOUT: Duration = Duration::from_secs(4);
 
 struct Model {
     link: ComponentLink<Self>,
 
-    ws: Option<WebSocketTask>,
-
-    lobby_chat_input: String,
+    connection: Box<dyn Bridge<Connection>>,
+    status: Status,
+    router: RouteAgentDispatcher,
 
-    requests: Requests,
+    /// The player's name and session, kept in local storage.
+    saved: Saved,
+    /// Who the server says we are in the current lobby.
+    me: Option<messages::Player>,
 
     lobby: Option<messages::Lobby>,
-    chat_messages: Vec<messages::LobbyMessageResponse>,
+    chat: Chat,
+    /// When to give up on each of our chat messages the server hasn't echoed back yet.
+    chat_timeouts: HashMap<String, TimeoutTask>,
+    /// Reactions being shown, each until its timeout fires.
+    reactions: Vec<messages::LobbyReactionResponse>,
+    reaction_timeouts: HashMap<String, TimeoutTask>,
+    game: Option<messages::GameView>,
+    /// Kept here rather than in a scene so it stays put when the route changes.
+    scores: Option<messages::ScoreUpdate>,
 }
 
 impl Model {
     fn view_connection_status(&self) -> Html {
-        if self.ws.is_some() {
-            html! {
-                <div class="flex justify-end p-4 shadow-md">
-                    <p>{ "✓" }</p>
-                    <button
-                        class="w-32 bg-red-200 hover:bg-red-300 rounded-lg shadow-md"
-                        onclick=self.link.callback(|_| Msg::WsAction(WsAction::Disconnect))
-                    >
-                        { "Disconnect" }
-                    </button>
-                </div>
+        match self.status {
+            Status::Reconnecting(attempt) => {
+                html! {
+                    <div class="flex justify-end p-4 shadow-md">
+                        <p class="animate-pulse">{ format!("Reconnecting… (attempt {})", attempt) }</p>
+                        <button
+                            class="w-32 bg-red-200 hover:bg-red-300 rounded-lg shadow-md"
+                            onclick=self.link.callback(|_| Msg::Disconnect)
+                        >
+                            { "Stop" }
+                        </button>
+                    </div>
+                }
             }
-        } else {
-            html! {
-                <div class="flex justify-end p-4 shadow-md">
-                    <p>{ "𝙓" }</p>
-                    <button
-                        class="w-32 bg-blue-200 hover:bg-blue-300 rounded-lg shadow-md"
-                        onclick=self.link.callback(|_| Msg::WsAction(WsAction::Connect))
-                    >
-                        { "Connect" }
-                    </button>
-                </div>
+            Status::Connected | Status::Connecting => {
+                html! {
+                    <div class="flex justify-end p-4 shadow-md">
+                        <p>{ if self.status == Status::Connected { "✓" } else { "…" } }</p>
+                        <button
+                            class="w-32 bg-red-200 hover:bg-red-300 rounded-lg shadow-md"
+                            onclick=self.link.callback(|_| Msg::Disconnect)
+                        >
+                            { "Disconnect" }
+                        </button>
+                    </div>
+                }
+            }
+            Status::Disconnected => {
+                html! {
+                    <div class="flex justify-end p-4 shadow-md">
+                        <p>{ "𝙓" }</p>
+                        <button
+                            class="w-32 bg-blue-200 hover:bg-blue-300 rounded-lg shadow-md"
+                            onclick=self.link.callback(|_| Msg::Connect)
+                        >
+                            { "Connect" }
+                        </button>
+                    </div>
+                }
             }
         }
     }
-}
 
-enum WsAction {
-    Connect,
-    SendLobbyMessage(Value),
-    SendLobbyCreate(Value),
-    SendLobbyJoin(Value),
-    Disconnect,
-    Lost,
+    fn remember_session(&mut self, lobby: &messages::Lobby, session_token: String) {
+        self.saved.session = Some(Session {
+            lobby_code: lobby.id.clone(),
+            session_token,
+        });
+        storage::save(&self.saved);
+    }
+
+    /// Show the lobby we are now in, at its own URL so it can be shared.
+    fn enter_lobby(&mut self, lobby: messages::Lobby) {
+        if self.lobby.as_ref().map(|l| &l.id) != Some(&lobby.id) {
+            let route = AppRoute::Lobby(lobby.id.clone()).into_route();
+            self.router.send(RouteRequest::ChangeRoute(route));
+            self.chat = Chat::default();
+        }
+        self.lobby = Some(lobby);
+    }
+
+    /// Forget the lobby we were removed from and go back to the start.
+    fn leave_lobby(&mut self) {
+        self.lobby = None;
+        self.me = None;
+        self.game = None;
+        self.scores = None;
+        self.chat = Chat::default();
+        self.saved.session = None;
+        storage::save(&self.saved);
+        self.router
+            .send(RouteRequest::ChangeRoute(AppRoute::Home.into_route()));
+    }
+
+    /// Ask the server to put us back where we were before the connection dropped.
+    fn resume(&mut self) {
+        if let (Some(_), Some(session)) = (&self.lobby, &self.saved.session) {
+            let message = messages::Message::Resume(messages::Resume {
+                lobby_code: session.lobby_code.clone(),
+                session_token: session.session_token.clone(),
+            });
+            self.connection.send(connection::Request::Send(message));
+        }
+    }
+
+    fn receive(&mut self, message: messages::Message) {
+        match message {
+            messages::Message::LobbyActionCreateResponse(m) => {
+                self.me = Some(m.you);
+                self.remember_session(&m.lobby, m.session_token);
+                self.enter_lobby(m.lobby);
+            }
+            messages::Message::LobbyActionJoinResponse(m) => {
+                self.remember_session(&m.lobby, m.session_token);
+                self.me = Some(m.you);
+                self.enter_lobby(m.lobby);
+                self.chat.history(m.history, m.more_history);
+            }
+            messages::Message::LobbyUpdate(m) => {
+                let still_in = match &self.me {
+                    Some(me) => m
+                        .lobby
+                        .players
+                        .iter()
+                        .any(|p| p.connection_id == me.connection_id),
+                    None => false,
+                };
+                if still_in {
+                    self.enter_lobby(m.lobby);
+                } else {
+                    self.leave_lobby();
+                }
+            }
+            messages::Message::ResumeResponse(m) => {
+                self.me = Some(m.you);
+                self.enter_lobby(m.lobby);
+                self.chat.history(m.history, m.more_history);
+            }
+            messages::Message::LobbyMessageResponse(m) => {
+                if let Some(client_id) = &m.client_id {
+                    self.chat_timeouts.remove(client_id);
+                }
+                self.chat.received(m);
+            }
+            messages::Message::SystemMessage(m) => {
+                self.chat.system(m);
+            }
+            messages::Message::LobbyMessageHistoryResponse(m) => {
+                self.chat.history(m.messages, m.more);
+            }
+            messages::Message::LobbyReactionResponse(m) => {
+                let id = m.id.clone();
+                let timeout = TimeoutService::spawn(
+                    REACTION_TIMEOUT,
+                    self.link
+                        .callback(move |_| Msg::ReactionTimeout(id.clone())),
+                );
+                self.reaction_timeouts.insert(m.id.clone(), timeout);
+                self.reactions.push(m);
+            }
+            messages::Message::GameUpdate(m) => {
+                self.game = Some(m.game);
+            }
+            messages::Message::ScoreUpdate(m) => {
+                self.scores = Some(m);
+            }
+            messages::Message::Error(e) => {
+                log::error!("Server rejected a message: {:?}", e);
+            }
+            _ => {
+                log::error!("Received non-matched message");
+            }
+        }
+    }
 }
 
 enum Msg {
-    Ignore,
-    WsAction(WsAction),
-    WsReady(Result<messages::Message, Error>),
-    LobbyChatInputChange(String),
+    SetName(String),
+    Connect,
+    Disconnect,
+    Connection(connection::Response),
+    ChatTimeout(String),
+    ReactionTimeout(String),
 }
 
 impl Component for Model {
@@ -85,90 +229,66 @@ impl Component for Model {
     type Properties = ();
 
     fn create(_: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let connection = Connection::bridge(link.callback(Msg::Connection));
         Self {
             link,
+            connection,
+            status: Status::Disconnected,
+            router: RouteAgentDispatcher::new(),
+            saved: storage::load(),
+            me: None,
             lobby: None,
-            lobby_chat_input: "".to_owned(),
-            requests: Requests {
-                creating_lobby: false,
-                joining_lobby: false,
-            },
-            chat_messages: vec![],
-            ws: None,
+            chat: Chat::default(),
+            chat_timeouts: HashMap::new(),
+            reactions: Vec::new(),
+            reaction_timeouts: HashMap::new(),
+            game: None,
+            scores: None,
         }
     }
 
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
         match msg {
-            Msg::Ignore => (),
-            Msg::WsAction(action) => match action {
-                WsAction::Connect => {
-                    let callback = self.link.callback(|Json(data)| Msg::WsReady(data));
-                    let notification = self.link.callback(|status| match status {
-                        WebSocketStatus::Opened => Msg::Ignore,
-                        WebSocketStatus::Closed | WebSocketStatus::Error => {
-                            Msg::WsAction(WsAction::Lost.into())
-                        }
-                    });
-                    let task = WebSocketService::connect(
-                        "wss://rse5mmis8e.execute-api.ca-central-1.amazonaws.com/dev",
-                        callback,
-                        notification,
-                    )
-                    .unwrap();
-                    self.ws = Some(task);
-                }
-                WsAction::SendLobbyCreate(data) => {
-                    log::info!("Sending data");
-                    self.requests.creating_lobby = true;
-                    self.ws.as_mut().unwrap().send(Json(&data));
-                }
-                WsAction::SendLobbyJoin(data) => {
-                    log::info!("Sending data");
-                    self.requests.joining_lobby = true;
-                    self.ws.as_mut().unwrap().send(Json(&data));
-                }
-                WsAction::SendLobbyMessage(data) => {
-                    log::info!("Sending data");
-                    // Optimistic UI. We assume that anything we send will be received
-                    self.chat_messages.push(messages::LobbyMessageResponse {
-                        name: "wat".to_owned(),
-                        body: self.lobby_chat_input.clone(),
-                    });
-                    self.lobby_chat_input = "".to_owned();
-                    self.ws.as_mut().unwrap().send(Json(&data));
-                }
-                WsAction::Disconnect => {
-                    log::info!("Disconnecting from WebSocket");
-                    self.ws.take();
-                }
-                WsAction::Lost => {
-                    log::info!("WebSocket connection lost.");
-                    self.ws = None;
+            Msg::SetName(name) => {
+                let name = name.trim().to_owned();
+                self.saved.name = if name.is_empty() { None } else { Some(name) };
+                storage::save(&self.saved);
+            }
+            Msg::Connect => self.connection.send(connection::Request::Connect),
+            Msg::Disconnect => self.connection.send(connection::Request::Disconnect),
+            Msg::Connection(connection::Response::Status(status)) => {
+                let reconnected = match self.status {
+                    Status::Reconnecting(_) => status == Status::Connected,
+                    _ => false,
+                };
+                self.status = status;
+                if reconnected {
+                    self.resume();
                 }
-            },
-            Msg::WsReady(response) => {
-                log::info!("Received message from WebSocket, {:?}", &response);
-                match response.expect("Received bad message") {
-                    messages::Message::LobbyActionCreateResponse(m) => {
-                        self.requests.creating_lobby = false;
-                        self.lobby = Some(m.lobby);
-                    }
-                    messages::Message::LobbyActionJoinResponse(m) => {
-                        self.requests.joining_lobby = false;
-                        self.lobby = Some(m.lobby);
-                    }
-                    messages::Message::LobbyMessageResponse(m) => {
-                        self.chat_messages.push(m);
-                    }
-                    _ => {
-                        log::error!("Received non-matched message");
-                    }
+            }
+            Msg::Connection(connection::Response::Sent(messages::Message::LobbyMessage(m))) => {
+                // Show our message straight away, until the server echoes it back.
+                if let Some(client_id) = m.client_id {
+                    let timed_out = client_id.clone();
+                    let timeout = TimeoutService::spawn(
+                        CHAT_TIMEOUT,
+                        self.link
+                            .callback(move |_| Msg::ChatTimeout(timed_out.clone())),
+                    );
+                    self.chat_timeouts.insert(client_id.clone(), timeout);
+                    self.chat.sent(client_id, m.body, m.to);
                 }
             }
-            Msg::LobbyChatInputChange(new_value) => {
-                self.lobby_chat_input = new_value;
+            Msg::ChatTimeout(client_id) => {
+                self.chat_timeouts.remove(&client_id);
+                self.chat.failed(&client_id);
             }
+            Msg::ReactionTimeout(id) => {
+                self.reaction_timeouts.remove(&id);
+                self.reactions.retain(|r| r.id != id);
+            }
+            Msg::Connection(connection::Response::Sent(_)) => return false,
+            Msg::Connection(connection::Response::Received(message)) => self.receive(message),
         }
         true
     }
@@ -180,28 +300,44 @@ impl Component for Model {
         false
     }
 
-    fn view(&'static self) -> Html {
+    fn view(&self) -> Html {
+        // The router keeps its render function around, so it gets its own copy of the state.
+        let lobby = self.lobby.clone();
+        let me = self.me.clone();
+        let chat = self.chat.clone();
+        let reactions = self.reactions.clone();
+        let game = self.game.clone();
+        let name = self.saved.name.clone();
+        let session = self.saved.session.clone();
+        let on_set_name = self.link.callback(Msg::SetName);
         html! {
             <div class="w-auto h-screen bg-green-50">
                 <div class="flex flex-col h-screen justify-between container mx-auto">
                     <main>
                         <AppRouter
-                            render=AppRouter::render(|switch: PublicUrlSwitch| {
+                            render=AppRouter::render(move |switch: PublicUrlSwitch| {
                                 match switch.route() {
                                     AppRoute::Lobby(lobby_code) => {
                                         html! {
                                             <Lobby
                                                 lobby_code=lobby_code
-                                                lobby=&self.lobby
-                                                chat_messages=&self.chat_messages
+                                                lobby=&lobby
+                                                me=&me
+                                                chat=&chat
+                                                reactions=&reactions
+                                                game=&game
+                                                name=&name
+                                                on_set_name=on_set_name.clone()
+                                                session=&session
                                             />
                                         }
                                     }
                                     AppRoute::Home => {
                                         html! {
                                             <OutOfLobby
-                                                request_joining_lobby=&self.requests.joining_lobby
-                                                request_creating_lobby=&self.requests.creating_lobby
+                                                name=&name
+                                                on_set_name=on_set_name.clone()
+                                                last_lobby_code=session.as_ref().map(|s| s.lobby_code.clone())
                                             />
                                         }
                                     }
@@ -215,6 +351,12 @@ impl Component for Model {
                             })
                         />
                     </main>
+                    {
+                        match &self.scores {
+                            Some(scores) => html! { <Scoreboard scores=scores /> },
+                            None => html! {},
+                        }
+                    }
                     <footer>{ self.view_connection_status() }</footer>
                 </div>
             </div>
@@ -224,7 +366,7 @@ impl Component for Model {
     fn rendered(&mut self, first_render: bool) {
         if first_render {
             log::info!("Connecting to Websocket");
-            self.link.send_message(Msg::WsAction(WsAction::Connect));
+            self.link.send_message(Msg::Connect);
         }
     }
 }
@@ -1,35 +1,140 @@
-use serde_json::json;
+use game::{Card, Phase};
+use messages::{Reaction, Recipient};
+use yew::agent::{Bridge, Bridged};
 use yew::prelude::*;
 use yewtil::NeqAssign;
 
+use crate::agents::connection::{self, Connection};
+use crate::chat::{self, Chat, ChatLine};
+use crate::scenes::{passing::Passing, table::Table};
+use crate::storage::Session;
+
 #[derive(Clone, Debug, PartialEq, Properties)]
 pub struct Props {
     pub lobby_code: String,
     pub lobby: Option<messages::Lobby>,
-    pub chat_messages: Vec<messages::LobbyMessageResponse>,
+    /// Who we are in the lobby, once we are in it.
+    pub me: Option<messages::Player>,
+    pub chat: Chat,
+    /// Reactions to show right now, to the trick or to chat messages.
+    pub reactions: Vec<messages::LobbyReactionResponse>,
+    pub game: Option<messages::GameView>,
+    /// The name the player picked, once they have.
+    pub name: Option<String>,
+    pub on_set_name: Callback<String>,
+    /// The place we last had in a lobby, which is taken back rather than joining again.
+    pub session: Option<Session>,
 }
 
 pub struct Lobby {
     link: ComponentLink<Self>,
     pub props: Props,
 
+    connection: Box<dyn Bridge<Connection>>,
+    connected: bool,
+    /// The lobby code we last asked to join, so it is only asked once.
+    join_requested: Option<String>,
+    /// Whether we asked to resume a session and are still waiting to hear back.
+    resuming: bool,
+    /// Whether the server refused our saved session, so we have to join as someone new.
+    resume_failed: bool,
+
     lobby_chat_input: String,
+    /// Why the server refused our last chat message.
+    chat_error: Option<String>,
+    /// Who our chat messages go to, or everyone.
+    chat_to: Option<Recipient>,
+    /// Why the server refused our last change to the lobby.
+    lobby_error: Option<String>,
+    /// The seat the host picked to swap with the next one they pick.
+    swap_from: Option<usize>,
+    name_input: String,
 }
 
 impl Lobby {
+    /// Get into the lobby in the URL, unless we are already in it.
+    ///
+    /// A saved session for the lobby is resumed, otherwise we join once we have a name.
+    fn join_if_needed(&mut self) {
+        let lobby_code = self.props.lobby_code.clone();
+        let in_lobby = self.props.lobby.as_ref().map(|l| &l.id) == Some(&lobby_code);
+        if !self.connected || in_lobby || self.join_requested.as_ref() == Some(&lobby_code) {
+            return;
+        }
+        let message = match (&self.props.session, &self.props.name) {
+            (Some(session), _) if session.lobby_code == lobby_code && !self.resume_failed => {
+                messages::Message::Resume(messages::Resume {
+                    lobby_code: lobby_code.clone(),
+                    session_token: session.session_token.clone(),
+                })
+            }
+            (_, Some(name)) => messages::Message::LobbyActionJoin(messages::LobbyActionJoin {
+                name: name.clone(),
+                lobby_code: lobby_code.clone(),
+            }),
+            _ => return,
+        };
+        self.join_requested = Some(lobby_code);
+        self.send(message);
+    }
+
+    fn view_name_input(&self) -> Html {
+        html! {
+            <div class="flex flex-col p-2 bg-white shadow-lg">
+                <p>{ format!("Pick a name to join lobby {}", self.props.lobby_code) }</p>
+                <div class="flex flex-row">
+                    <input
+                        value=&self.name_input
+                        oninput=self.link.callback(|e: InputData| Msg::NameInputChange(e.value))
+                        maxlength=messages::NAME_MAX_LENGTH.to_string()
+                        placeholder="Pick a name your friends will see."
+                        class="m-4 p-2 flex-grow focus:ring-2 focus:ring-blue-600 rounded-lg shadow-md"
+                    />
+                    <button
+                        class="w-32 m-4 disabled:opacity-50 bg-blue-200 hover:bg-blue-300 rounded-lg shadow-md"
+                        disabled=self.name_input.is_empty()
+                        onclick=self.link.callback(|_| Msg::NameInputConfirm)
+                    >
+                        { "Join" }
+                    </button>
+                </div>
+            </div>
+        }
+    }
+
     fn view_lobby(&self) -> Html {
         if let Some(lobby) = &self.props.lobby {
-            let player_names = lobby
-                .players
-                .iter()
-                .map(|p| p.name.clone())
-                .collect::<Vec<String>>()
-                .join(", ");
-
+            let lock = if self.is_host() {
+                let locked = !lobby.locked;
+                html! {
+                    <button
+                        class="text-xs ml-2 underline"
+                        onclick=self.link.callback(move |_| Msg::Lock(locked))
+                    >
+                        { if lobby.locked { "Unlock" } else { "Lock" } }
+                    </button>
+                }
+            } else {
+                html! {}
+            };
             html! {
                 <div>
-                    <p>{ "Lobby code:" }{ lobby.id.clone() }</p>
-                    <p>{ "All players: "}{ player_names }</p>
+                    <p>
+                        { "Lobby code:" }{ lobby.id.clone() }
+                        { if lobby.locked { " (locked)" } else { "" } }
+                        { lock }
+                        <button
+                            class="text-xs ml-2 underline"
+                            onclick=self.link.callback(|_| Msg::Leave)
+                        >
+                            { "Leave" }
+                        </button>
+                    </p>
+                    { self.view_seats(lobby) }
+                    <p>{ "All players: "}</p>
+                    <ul>
+                        { for lobby.players.iter().map(|p| self.view_player(p)) }
+                    </ul>
                 </div>
             }
         } else {
@@ -37,44 +142,255 @@ impl Lobby {
         }
     }
 
+    /// The players in each seat, which players can sit in while they are open and the host can
+    /// swap between.
+    fn view_seats(&self, lobby: &messages::Lobby) -> Html {
+        let can_move = !self.in_progress();
+        html! {
+            <div class="flex flex-row space-x-2">
+                {
+                    for game::SEAT_NAMES.iter().enumerate().map(|(seat, seat_name)| {
+                        let player = self.seated(seat);
+                        let sit = if can_move && player.is_none() && self.props.me.is_some() {
+                            html! {
+                                <button
+                                    class="text-xs ml-2 underline"
+                                    onclick=self.link.callback(move |_| Msg::TakeSeat(seat))
+                                >
+                                    { "Sit here" }
+                                </button>
+                            }
+                        } else {
+                            html! {}
+                        };
+                        let swap = match self.swap_from {
+                            _ if !can_move || !self.is_host() => html! {},
+                            Some(from) if from == seat => html! {
+                                <button
+                                    class="text-xs ml-2 underline"
+                                    onclick=self.link.callback(|_| Msg::SwapFrom(None))
+                                >
+                                    { "Cancel" }
+                                </button>
+                            },
+                            Some(from) => html! {
+                                <button
+                                    class="text-xs ml-2 underline"
+                                    onclick=self.link.callback(move |_| Msg::SwapSeats(from, seat))
+                                >
+                                    { "Swap here" }
+                                </button>
+                            },
+                            None => html! {
+                                <button
+                                    class="text-xs ml-2 underline"
+                                    onclick=self.link.callback(move |_| Msg::SwapFrom(Some(seat)))
+                                >
+                                    { "Move" }
+                                </button>
+                            },
+                        };
+                        html! {
+                            <div class="p-2 bg-white rounded-lg shadow-md">
+                                <p class="text-xs">{ seat_name }</p>
+                                <p>{ player.map_or("Open".to_owned(), |p| p.name.clone()) }</p>
+                                { sit }
+                                { swap }
+                            </div>
+                        }
+                    })
+                }
+            </div>
+        }
+    }
+
+    /// The player sitting in `seat`.
+    fn seated(&self, seat: usize) -> Option<&messages::Player> {
+        let lobby = self.props.lobby.as_ref()?;
+        let connection_id = lobby.seats.get(seat)?.as_ref()?;
+        lobby
+            .players
+            .iter()
+            .find(|p| &p.connection_id == connection_id)
+    }
+
+    fn in_progress(&self) -> bool {
+        match &self.props.game {
+            Some(game) => game.phase != Phase::Finished,
+            None => false,
+        }
+    }
+
+    fn is_host(&self) -> bool {
+        match (&self.props.lobby, &self.props.me) {
+            (Some(lobby), Some(me)) => lobby.host == me.connection_id,
+            _ => false,
+        }
+    }
+
+    fn view_player(&self, player: &messages::Player) -> Html {
+        let is_me =
+            self.props.me.as_ref().map(|me| &me.connection_id) == Some(&player.connection_id);
+        let is_host = self
+            .props
+            .lobby
+            .as_ref()
+            .map_or(false, |l| l.host == player.connection_id);
+        let controls = if self.is_host() && !is_me {
+            let (name, muted) = (player.name.clone(), !player.muted);
+            let (kick_name, host_name) = (player.name.clone(), player.name.clone());
+            html! {
+                <>
+                    <button
+                        class="text-xs ml-2 underline"
+                        onclick=self.link.callback(move |_| Msg::Mute(name.clone(), muted))
+                    >
+                        { if player.muted { "Unmute" } else { "Mute" } }
+                    </button>
+                    <button
+                        class="text-xs ml-2 underline"
+                        onclick=self.link.callback(move |_| Msg::Kick(kick_name.clone()))
+                    >
+                        { "Kick" }
+                    </button>
+                    <button
+                        class="text-xs ml-2 underline"
+                        onclick=self.link.callback(move |_| Msg::MakeHost(host_name.clone()))
+                    >
+                        { "Make host" }
+                    </button>
+                </>
+            }
+        } else {
+            html! {}
+        };
+        html! {
+            <li>
+                { player.name.clone() }
+                { if is_host { " (host)" } else { "" } }
+                { if player.away { " (away)" } else { "" } }
+                { if player.ready { " (ready)" } else { "" } }
+                { if player.muted { " (muted)" } else { "" } }
+                { controls }
+            </li>
+        }
+    }
+
+    fn view_game(&self) -> Html {
+        let in_progress = self.in_progress();
+        let seated: Vec<&messages::Player> = (0..game::PLAYERS)
+            .filter_map(|seat| self.seated(seat))
+            .collect();
+        let full = seated.len() == game::PLAYERS;
+        let all_ready = full && seated.iter().all(|p| p.ready);
+        let auto_start = self.props.lobby.as_ref().map_or(false, |l| l.auto_start);
+        let table = match &self.props.game {
+            Some(game) if game.phase == Phase::Passing => {
+                html! { <Passing game=game on_pass=self.link.callback(Msg::Pass) /> }
+            }
+            Some(game) => {
+                let reactions: Vec<messages::LobbyReactionResponse> = self
+                    .props
+                    .reactions
+                    .iter()
+                    .filter(|r| r.message_id.is_none())
+                    .cloned()
+                    .collect();
+                html! {
+                    <Table
+                        game=game
+                        reactions=reactions
+                        on_play=self.link.callback(Msg::Play)
+                        on_react=self.link.callback(|reaction| Msg::React(reaction, None))
+                    />
+                }
+            }
+            None => html! {},
+        };
+        let me = self
+            .props
+            .me
+            .as_ref()
+            .and_then(|me| seated.iter().find(|p| p.connection_id == me.connection_id));
+        let ready = match me {
+            Some(me) if !in_progress => {
+                let ready = !me.ready;
+                html! {
+                    <button
+                        class="w-32 m-4 bg-green-200 hover:bg-green-300 rounded-lg shadow-md"
+                        onclick=self.link.callback(move |_| Msg::Ready(ready))
+                    >
+                        { if me.ready { "Not ready" } else { "Ready" } }
+                    </button>
+                }
+            }
+            _ => html! {},
+        };
+        let auto = if self.is_host() && !in_progress {
+            html! {
+                <label class="m-4">
+                    <input
+                        type="checkbox"
+                        checked=auto_start
+                        onclick=self.link.callback(move |_| Msg::AutoStart(!auto_start))
+                    />
+                    { " Start when everyone is ready" }
+                </label>
+            }
+        } else {
+            html! {}
+        };
+        let start = if !in_progress && full && !auto_start {
+            html! {
+                <button
+                    class="w-32 m-4 disabled:opacity-50 bg-blue-200 hover:bg-blue-300 rounded-lg shadow-md"
+                    disabled=!all_ready
+                    onclick=self.link.callback(|_| Msg::Start)
+                >
+                    { "Start game" }
+                </button>
+            }
+        } else {
+            html! {}
+        };
+        html! {
+            <div>
+                { table }
+                { ready }
+                { start }
+                { auto }
+            </div>
+        }
+    }
+
     fn view_messages(&self) -> Html {
-        if let Some(lobby) = &self.props.lobby {
-            let send_lobby_message = json!({
-              "action": "send",
-              "lobby_code": lobby.id.clone(),
-              "body": self.lobby_chat_input.clone(),
-            });
+        if self.props.lobby.is_some() {
             html! {
                 <div>
                     <input
                         value=&self.lobby_chat_input
                         oninput=self.link.callback(|e: InputData| Msg::LobbyChatInputChange(e.value))
+                        maxlength=messages::CHAT_MAX_LENGTH.to_string()
                         placeholder="Say something.."
                         class="m-4 focus:ring-2 focus:ring-blue-600 rounded-lg shadow-md"
                     />
+                    { self.view_chat_to() }
                     <button
                         class="w-32 m-4 disabled:opacity-50 bg-blue-200 hover:bg-blue-300 rounded-lg shadow-md"
                         disabled={self.lobby_chat_input.is_empty()}
-                        onclick=self.link.callback(move |_| {
-                            Msg::Ignore
-                            // Msg::WsAction(WsAction::SendLobbyMessage(send_lobby_message.clone()))
-                        })
+                        onclick=self.link.callback(|_| Msg::SendChat)
                     >
                         { "Send" }
                     </button>
-                    <div>
                     {
-                        for self.props.chat_messages.iter().map(|m| {
-                            html! {
-                                <div class="flex">
-                                    <div class="m4 bg-green-200 rounded-lg">
-                                        { m.name.clone() }
-                                    </div>
-                                    { m.body.clone() }
-                                </div>
-                            }
-                        })
+                        match &self.chat_error {
+                            Some(error) => html! { <p class="text-red-600">{ error.clone() }</p> },
+                            None => html! {},
+                        }
                     }
+                    <div>
+                    { self.view_load_older() }
+                    { for self.props.chat.lines.iter().map(|line| self.view_chat_line(line)) }
                     </div>
                 </div>
             }
@@ -82,11 +398,187 @@ impl Lobby {
             html! {}
         }
     }
+
+    /// Picks who chat messages go to, as `everyone`, `team` or `player:` and a name.
+    fn view_chat_to(&self) -> Html {
+        let others: Vec<String> = match &self.props.lobby {
+            Some(lobby) => lobby
+                .players
+                .iter()
+                .filter(|p| {
+                    Some(&p.connection_id) != self.props.me.as_ref().map(|me| &me.connection_id)
+                })
+                .map(|p| p.name.clone())
+                .collect(),
+            None => Vec::new(),
+        };
+        let selected = match &self.chat_to {
+            None => "everyone".to_owned(),
+            Some(Recipient::Team) => "team".to_owned(),
+            Some(Recipient::Player { name }) => format!("player:{}", name),
+        };
+        html! {
+            <select
+                class="m-4 rounded-lg shadow-md"
+                onchange=self.link.callback(|e: ChangeData| match e {
+                    ChangeData::Select(select) => Msg::ChatToChange(select.value()),
+                    _ => Msg::ChatToChange("everyone".to_owned()),
+                })
+            >
+                <option value="everyone" selected={selected == "everyone"}>{ "Everyone" }</option>
+                <option value="team" selected={selected == "team"}>{ "Partner" }</option>
+                {
+                    for others.iter().map(|name| {
+                        let value = format!("player:{}", name);
+                        html! {
+                            <option value=value.clone() selected={selected == value}>
+                                { format!("Only {}", name) }
+                            </option>
+                        }
+                    })
+                }
+            </select>
+        }
+    }
+
+    fn view_load_older(&self) -> Html {
+        if self.props.chat.more_history {
+            html! {
+                <button
+                    class="text-xs text-gray-500 underline"
+                    onclick=self.link.callback(|_| Msg::LoadOlderChat)
+                >
+                    { "Load older messages" }
+                </button>
+            }
+        } else {
+            html! {}
+        }
+    }
+
+    fn view_chat_line(&self, line: &ChatLine) -> Html {
+        match line {
+            ChatLine::Confirmed(m) => html! {
+                <div class="flex">
+                    <div class="text-xs text-gray-500 mr-2">{ m.sent_at.format("%H:%M").to_string() }</div>
+                    <div class={ if m.to.is_some() { "m4 bg-purple-200 rounded-lg" } else { "m4 bg-green-200 rounded-lg" } }>
+                        { m.name.clone() }
+                    </div>
+                    {
+                        match &m.to {
+                            Some(Recipient::Player { name }) => format!(" to {}: ", name),
+                            Some(Recipient::Team) => " to partner: ".to_owned(),
+                            None => "".to_owned(),
+                        }
+                    }
+                    { m.body.clone() }
+                    { self.view_message_reactions(&m.id) }
+                </div>
+            },
+            ChatLine::System(m) => html! {
+                <div class="flex italic text-gray-600">
+                    <div class="text-xs text-gray-500 mr-2">{ m.sent_at.format("%H:%M").to_string() }</div>
+                    { m.body.clone() }
+                </div>
+            },
+            ChatLine::Pending { body, .. } => html! {
+                <div class="flex opacity-50">
+                    <div class="m4 bg-green-200 rounded-lg">{ "You" }</div>
+                    { body.clone() }
+                    <div class="text-xs ml-2">{ "sending…" }</div>
+                </div>
+            },
+            ChatLine::Failed {
+                client_id,
+                body,
+                to,
+            } => {
+                let (client_id, resend_body, to) = (client_id.clone(), body.clone(), to.clone());
+                html! {
+                    <div class="flex text-red-600">
+                        <div class="m4 bg-red-200 rounded-lg">{ "You" }</div>
+                        { body.clone() }
+                        <button
+                            class="text-xs ml-2 underline"
+                            onclick=self.link.callback(move |_| Msg::Resend(client_id.clone(), resend_body.clone(), to.clone()))
+                        >
+                            { "Not sent, resend" }
+                        </button>
+                    </div>
+                }
+            }
+        }
+    }
+
+    /// The reactions being shown for the chat message with `id`, and buttons to add one.
+    fn view_message_reactions(&self, id: &String) -> Html {
+        let shown = self
+            .props
+            .reactions
+            .iter()
+            .filter(|r| r.message_id.as_ref() == Some(id));
+        html! {
+            <div class="flex text-xs ml-2 space-x-1">
+                {
+                    for shown.map(|r| html! {
+                        <span class="px-1 bg-yellow-100 rounded-full">
+                            { format!("{} {}", r.name, r.reaction.text()) }
+                        </span>
+                    })
+                }
+                {
+                    for Reaction::ALL.iter().map(|&reaction| {
+                        let id = id.clone();
+                        html! {
+                            <button
+                                class="opacity-50 hover:opacity-100"
+                                onclick=self.link.callback(move |_| Msg::React(reaction, Some(id.clone())))
+                            >
+                                { reaction.text() }
+                            </button>
+                        }
+                    })
+                }
+            </div>
+        }
+    }
+
+    /// Send a message about this lobby to the server.
+    fn send(&mut self, message: messages::Message) {
+        self.connection.send(connection::Request::Send(message));
+    }
+
+    fn lobby_code(&self) -> String {
+        match &self.props.lobby {
+            Some(lobby) => lobby.id.clone(),
+            None => self.props.lobby_code.clone(),
+        }
+    }
 }
 
 enum Msg {
-    Ignore,
+    Connection(connection::Response),
     LobbyChatInputChange(String),
+    NameInputChange(String),
+    NameInputConfirm,
+    SendChat,
+    Resend(String, String, Option<Recipient>),
+    LoadOlderChat,
+    Mute(String, bool),
+    Kick(String),
+    MakeHost(String),
+    Lock(bool),
+    Leave,
+    TakeSeat(usize),
+    SwapFrom(Option<usize>),
+    SwapSeats(usize, usize),
+    Ready(bool),
+    AutoStart(bool),
+    ChatToChange(String),
+    React(Reaction, Option<String>),
+    Start,
+    Play(Card),
+    Pass(Vec<Card>),
 }
 
 impl Component for Lobby {
@@ -94,31 +586,274 @@ impl Component for Lobby {
     type Properties = Props;
 
     fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        // Everything the server sends is handled by the app, this only needs to know when the
+        // connection is open.
+        let connection = Connection::bridge(link.callback(Msg::Connection));
         Self {
             link,
             props,
+            connection,
+            connected: false,
+            join_requested: None,
+            resuming: false,
+            resume_failed: false,
             lobby_chat_input: "".to_owned(),
+            chat_error: None,
+            chat_to: None,
+            lobby_error: None,
+            swap_from: None,
+            name_input: "".to_owned(),
         }
     }
 
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
         match msg {
-            Msg::Ignore => {}
+            Msg::Connection(connection::Response::Status(status)) => {
+                self.connected = status == connection::Status::Connected;
+                self.join_if_needed();
+            }
+            Msg::Connection(connection::Response::Sent(messages::Message::Resume(_))) => {
+                self.resuming = true;
+                return false;
+            }
+            Msg::Connection(connection::Response::Received(messages::Message::ResumeResponse(
+                _,
+            ))) => {
+                self.resuming = false;
+                return false;
+            }
+            Msg::Connection(connection::Response::Received(messages::Message::Error(e)))
+                if e.kind == messages::ErrorKind::PlayerNotFound && self.resuming =>
+            {
+                // Our place in the lobby is gone, so join it again as someone new.
+                self.resuming = false;
+                self.resume_failed = true;
+                self.join_requested = None;
+                self.join_if_needed();
+            }
+            Msg::Connection(connection::Response::Received(messages::Message::Error(e)))
+                if e.kind == messages::ErrorKind::LobbyNotFound =>
+            {
+                self.resuming = false;
+                self.lobby_error = Some(format!(
+                    "There is no lobby {}, it may have closed",
+                    self.props.lobby_code
+                ));
+            }
+            Msg::Connection(connection::Response::Received(messages::Message::Error(e)))
+                if e.kind == messages::ErrorKind::InvalidName =>
+            {
+                // Ask for another name, and join with it once it is picked.
+                self.lobby_error = Some(e.message);
+                self.join_requested = None;
+                self.props.on_set_name.emit(String::new());
+            }
+            Msg::Connection(connection::Response::Received(messages::Message::Error(e))) => {
+                match e.kind {
+                    messages::ErrorKind::MessageTooLong
+                    | messages::ErrorKind::RateLimited
+                    | messages::ErrorKind::Muted
+                    | messages::ErrorKind::BlockedWord
+                    | messages::ErrorKind::InvalidMessage => self.chat_error = Some(e.message),
+                    messages::ErrorKind::LobbyLocked
+                    | messages::ErrorKind::InvalidAction
+                    | messages::ErrorKind::NotReady
+                    | messages::ErrorKind::TryAgain => self.lobby_error = Some(e.message),
+                    _ => return false,
+                }
+            }
+            Msg::Connection(_) => return false,
+            Msg::NameInputChange(new_value) => {
+                self.name_input = new_value;
+            }
+            Msg::NameInputConfirm => {
+                self.props.on_set_name.emit(self.name_input.clone());
+            }
             Msg::LobbyChatInputChange(new_value) => {
                 self.lobby_chat_input = new_value;
             }
+            Msg::SendChat => {
+                let body = std::mem::take(&mut self.lobby_chat_input);
+                self.chat_error = None;
+                // Commands are answered rather than echoed, so there is nothing to wait for.
+                let client_id = if body.starts_with('/') {
+                    None
+                } else {
+                    Some(chat::next_client_id())
+                };
+                self.send(messages::Message::LobbyMessage(messages::LobbyMessage {
+                    lobby_code: self.lobby_code(),
+                    body,
+                    client_id,
+                    to: self.chat_to.clone(),
+                }));
+            }
+            Msg::Resend(client_id, body, to) => {
+                self.send(messages::Message::LobbyMessage(messages::LobbyMessage {
+                    lobby_code: self.lobby_code(),
+                    body,
+                    client_id: Some(client_id),
+                    to,
+                }));
+            }
+            Msg::LoadOlderChat => {
+                self.send(messages::Message::LobbyMessageHistory(
+                    messages::LobbyMessageHistory {
+                        lobby_code: self.lobby_code(),
+                        before: self.props.chat.oldest_id().cloned(),
+                    },
+                ));
+            }
+            Msg::ChatToChange(value) => {
+                self.chat_to = match value.as_str() {
+                    "team" => Some(Recipient::Team),
+                    other => other.strip_prefix("player:").map(|name| Recipient::Player {
+                        name: name.to_owned(),
+                    }),
+                };
+            }
+            Msg::Mute(name, muted) => {
+                self.send(messages::Message::LobbyActionMute(
+                    messages::LobbyActionMute {
+                        lobby_code: self.lobby_code(),
+                        name,
+                        muted,
+                    },
+                ));
+            }
+            Msg::Kick(name) => {
+                self.lobby_error = None;
+                self.send(messages::Message::LobbyActionKick(
+                    messages::LobbyActionKick {
+                        lobby_code: self.lobby_code(),
+                        name,
+                    },
+                ));
+            }
+            Msg::MakeHost(name) => {
+                self.lobby_error = None;
+                self.send(messages::Message::LobbyActionTransferHost(
+                    messages::LobbyActionTransferHost {
+                        lobby_code: self.lobby_code(),
+                        name,
+                    },
+                ));
+            }
+            Msg::Lock(locked) => {
+                self.lobby_error = None;
+                self.send(messages::Message::LobbyActionLock(
+                    messages::LobbyActionLock {
+                        lobby_code: self.lobby_code(),
+                        locked,
+                    },
+                ));
+            }
+            Msg::Leave => {
+                self.send(messages::Message::LobbyActionLeave(
+                    messages::LobbyActionLeave {
+                        lobby_code: self.lobby_code(),
+                    },
+                ));
+            }
+            Msg::TakeSeat(seat) => {
+                self.lobby_error = None;
+                self.send(messages::Message::LobbyActionTakeSeat(
+                    messages::LobbyActionTakeSeat {
+                        lobby_code: self.lobby_code(),
+                        seat,
+                    },
+                ));
+            }
+            Msg::SwapFrom(seat) => {
+                self.swap_from = seat;
+            }
+            Msg::SwapSeats(first, second) => {
+                self.lobby_error = None;
+                self.swap_from = None;
+                self.send(messages::Message::LobbyActionSwapSeats(
+                    messages::LobbyActionSwapSeats {
+                        lobby_code: self.lobby_code(),
+                        first,
+                        second,
+                    },
+                ));
+            }
+            Msg::Ready(ready) => {
+                self.lobby_error = None;
+                self.send(messages::Message::LobbyActionReady(
+                    messages::LobbyActionReady {
+                        lobby_code: self.lobby_code(),
+                        ready,
+                    },
+                ));
+            }
+            Msg::AutoStart(auto_start) => {
+                self.lobby_error = None;
+                self.send(messages::Message::LobbyActionAutoStart(
+                    messages::LobbyActionAutoStart {
+                        lobby_code: self.lobby_code(),
+                        auto_start,
+                    },
+                ));
+            }
+            Msg::React(reaction, message_id) => {
+                self.send(messages::Message::LobbyReaction(messages::LobbyReaction {
+                    lobby_code: self.lobby_code(),
+                    reaction,
+                    message_id,
+                }));
+            }
+            Msg::Start => {
+                self.send(messages::Message::GameActionStart(
+                    messages::GameActionStart {
+                        lobby_code: self.lobby_code(),
+                    },
+                ));
+            }
+            Msg::Play(card) => {
+                self.send(messages::Message::GameActionPlay(
+                    messages::GameActionPlay {
+                        lobby_code: self.lobby_code(),
+                        card,
+                    },
+                ));
+            }
+            Msg::Pass(cards) => {
+                self.send(messages::Message::GameActionPass(
+                    messages::GameActionPass {
+                        lobby_code: self.lobby_code(),
+                        cards,
+                    },
+                ));
+            }
         }
         true
     }
 
     fn change(&mut self, props: Self::Properties) -> ShouldRender {
-        self.props.neq_assign(props)
+        let changed = self.props.neq_assign(props);
+        self.join_if_needed();
+        changed
     }
 
     fn view(&self) -> Html {
         html! {
             <div>
+                {
+                    if self.props.name.is_none() && self.props.lobby.is_none() {
+                        self.view_name_input()
+                    } else {
+                        html! {}
+                    }
+                }
+                {
+                    match &self.lobby_error {
+                        Some(error) => html! { <p class="text-red-600">{ error.clone() }</p> },
+                        None => html! {},
+                    }
+                }
                 <div>{ self.view_lobby() }</div>
+                <div>{ self.view_game() }</div>
                 <div>{ self.view_messages() }</div>
             </div>
         }
@@ -1,3 +1,5 @@
 pub mod lobby;
 pub mod out_of_lobby;
 pub mod page_not_found;
+pub mod passing;
+pub mod table;
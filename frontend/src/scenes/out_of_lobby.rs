@@ -1,22 +1,35 @@
-use serde_json::json;
+use yew::agent::{Bridge, Bridged};
 use yew::prelude::*;
+use yewtil::NeqAssign;
 
+use crate::agents::connection::{self, Connection};
 use crate::components::loading_spinner::LoadingSpinner;
+use crate::switch::{AppAnchor, AppRoute};
 
 #[derive(Clone, Debug, PartialEq, Properties)]
 pub struct Props {
-    pub request_joining_lobby: bool,
-    pub request_creating_lobby: bool,
+    /// The name the player picked, once they have.
+    pub name: Option<String>,
+    pub on_set_name: Callback<String>,
+    /// The lobby we were last in, which we may still have a seat in.
+    pub last_lobby_code: Option<String>,
+}
+
+struct Requests {
+    joining_lobby: bool,
+    creating_lobby: bool,
 }
 
 pub struct OutOfLobby {
     link: ComponentLink<Self>,
     props: Props,
+    connection: Box<dyn Bridge<Connection>>,
+    requests: Requests,
 
-    // on_set_name: Callback<String>,
-    name: String,
     lobby_code_input: String,
     name_input: String,
+    /// Why the server refused to create or join a lobby.
+    error: Option<String>,
 }
 
 impl OutOfLobby {
@@ -36,6 +49,7 @@ impl OutOfLobby {
                         id="name-input"
                         value=&self.name_input
                         oninput=self.link.callback(|e: InputData| Msg::NameInputChange(e.value))
+                        maxlength=messages::NAME_MAX_LENGTH.to_string()
                         placeholder="Pick a name your friends will see."
                         class="m-4 p-2 flex-grow focus:ring-2 focus:ring-blue-600 rounded-lg shadow-md"
                     />
@@ -54,21 +68,27 @@ impl OutOfLobby {
         }
     }
 
+    fn view_rejoin(&self) -> Html {
+        match &self.props.last_lobby_code {
+            Some(lobby_code) => html! {
+                <div class="flex flex-col p-2 bg-white shadow-lg">
+                    <AppAnchor
+                        classes="w-48 mx-auto my-4 py-2 text-center bg-green-200 hover:bg-green-300 rounded-lg shadow-md"
+                        route=AppRoute::Lobby(lobby_code.clone())
+                    >
+                        { format!("Rejoin your last game ({})", lobby_code) }
+                    </AppAnchor>
+                </div>
+            },
+            None => html! {},
+        }
+    }
+
     fn view_create_or_join(&self) -> Html {
-        let create_lobby_message = json!({
-          "action": "hearts",
-          "type": "lobby_action_create",
-          "name": self.name_input.clone(),
-        });
-        let join_lobby_message = json!({
-          "action": "hearts",
-          "type": "lobby_action_join",
-          "lobby_code": self.lobby_code_input.clone(),
-          "name": self.name_input.clone(),
-        });
         html! {
             <div class="flex flex-col p-2 bg-white shadow-lg">
                 <h2>{"Step 2"}</h2>
+                <p>{ format!("Playing as {}", self.props.name.clone().unwrap_or_default()) }</p>
 
                 <div class="flex flex-row">
                     <label
@@ -88,13 +108,10 @@ impl OutOfLobby {
                 <div class="flex flex-row">
                     <button
                         class="w-48 mx-auto my-4 py-2 disabled:opacity-50 bg-blue-200 hover:bg-blue-300 rounded-lg shadow-md"
-                        disabled={self.props.request_creating_lobby || self.props.request_joining_lobby}
-                        onclick=self.link.callback(move |_| {
-                            Msg::Ignore
-                            // Msg::WsAction(WsAction::SendLobbyJoin(join_lobby_message.clone()))
-                        })
+                        disabled={self.requests.creating_lobby || self.requests.joining_lobby}
+                        onclick=self.link.callback(|_| Msg::Join)
                     >
-                        { if self.props.request_joining_lobby { html!{ <LoadingSpinner /> } } else { html!{} } }
+                        { if self.requests.joining_lobby { html!{ <LoadingSpinner /> } } else { html!{} } }
                         { "Join lobby" }
                     </button>
                 </div>
@@ -106,13 +123,10 @@ impl OutOfLobby {
 
                 <button
                     class="w-48 mx-auto my-4 py-2 disabled:opacity-50 bg-blue-200 hover:bg-blue-300 rounded-lg shadow-md"
-                    disabled={self.props.request_creating_lobby || self.props.request_joining_lobby}
-                    onclick=self.link.callback(move |_| {
-                            Msg::Ignore
-                        // Msg::WsAction(WsAction::SendLobbyCreate(create_lobby_message.clone()))
-                    })
+                    disabled={self.requests.creating_lobby || self.requests.joining_lobby}
+                    onclick=self.link.callback(|_| Msg::Create)
                 >
-                    { if self.props.request_creating_lobby { html!{ <LoadingSpinner />} } else { html!{} } }
+                    { if self.requests.creating_lobby { html!{ <LoadingSpinner />} } else { html!{} } }
                     { "Create lobby" }
                 </button>
             </div>
@@ -121,7 +135,9 @@ impl OutOfLobby {
 }
 
 enum Msg {
-    Ignore,
+    Connection(connection::Response),
+    Create,
+    Join,
     NameInputConfirm,
     NameInputChange(String),
     LobbyCodeInputChange(String),
@@ -132,18 +148,59 @@ impl Component for OutOfLobby {
     type Properties = Props;
 
     fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let connection = Connection::bridge(link.callback(Msg::Connection));
         Self {
             link,
             props,
-            name: "".to_owned(),
+            connection,
+            requests: Requests {
+                creating_lobby: false,
+                joining_lobby: false,
+            },
             lobby_code_input: "".to_owned(),
             name_input: "".to_owned(),
+            error: None,
         }
     }
 
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
         match msg {
-            Msg::Ignore => {}
+            Msg::Connection(connection::Response::Received(message)) => match message {
+                messages::Message::LobbyActionCreateResponse(_)
+                | messages::Message::LobbyActionJoinResponse(_) => {
+                    self.requests.creating_lobby = false;
+                    self.requests.joining_lobby = false;
+                    self.error = None;
+                }
+                messages::Message::Error(e) => {
+                    self.requests.creating_lobby = false;
+                    self.requests.joining_lobby = false;
+                    // Ask for a name again if that is what was wrong.
+                    if e.kind == messages::ErrorKind::InvalidName {
+                        self.props.on_set_name.emit(String::new());
+                    }
+                    self.error = Some(e.message);
+                }
+                _ => return false,
+            },
+            Msg::Connection(_) => return false,
+            Msg::Create => {
+                self.requests.creating_lobby = true;
+                self.connection.send(connection::Request::Send(
+                    messages::Message::LobbyActionCreate(messages::LobbyActionCreate {
+                        name: self.props.name.clone().unwrap_or_default(),
+                    }),
+                ));
+            }
+            Msg::Join => {
+                self.requests.joining_lobby = true;
+                self.connection.send(connection::Request::Send(
+                    messages::Message::LobbyActionJoin(messages::LobbyActionJoin {
+                        name: self.props.name.clone().unwrap_or_default(),
+                        lobby_code: self.lobby_code_input.trim().to_lowercase(),
+                    }),
+                ));
+            }
             Msg::NameInputChange(new_value) => {
                 self.name_input = new_value;
             }
@@ -151,14 +208,14 @@ impl Component for OutOfLobby {
                 self.lobby_code_input = new_value;
             }
             Msg::NameInputConfirm => {
-                self.name = self.name_input.clone();
+                self.props.on_set_name.emit(self.name_input.clone());
             }
         }
         true
     }
 
-    fn change(&mut self, _props: Self::Properties) -> ShouldRender {
-        false
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props.neq_assign(props)
     }
 
     fn view(&self) -> Html {
@@ -176,7 +233,16 @@ impl Component for OutOfLobby {
                     <p class="text-center">{ "Not currently in a lobby." }</p>
                 </div>
 
-                { if self.name.is_empty() { self.view_name_input() } else { self.view_create_or_join() } }
+                { self.view_rejoin() }
+
+                {
+                    match &self.error {
+                        Some(error) => html! { <p class="text-center text-red-600">{ error.clone() }</p> },
+                        None => html! {},
+                    }
+                }
+
+                { if self.props.name.is_none() { self.view_name_input() } else { self.view_create_or_join() } }
 
             </>
         }
@@ -0,0 +1,156 @@
+use game::{Card, PassDirection, PASS_SIZE};
+use yew::prelude::*;
+use yewtil::NeqAssign;
+
+use crate::components::playing_card::PlayingCard;
+
+#[derive(Clone, Debug, PartialEq, Properties)]
+pub struct Props {
+    pub game: messages::GameView,
+    pub on_pass: Callback<Vec<Card>>,
+}
+
+/// Picking the three cards to pass before a hand is played.
+pub struct Passing {
+    link: ComponentLink<Self>,
+    props: Props,
+
+    selected: Vec<Card>,
+}
+
+pub enum Msg {
+    Toggle(Card),
+    Confirm,
+}
+
+impl Passing {
+    fn view_direction(&self) -> Html {
+        let game = &self.props.game;
+        let direction = match game.pass_direction {
+            PassDirection::Left => "left",
+            PassDirection::Right => "right",
+            PassDirection::Across => "across",
+            PassDirection::Hold => "nobody",
+        };
+        let target = game
+            .pass_direction
+            .target(game.seat)
+            .and_then(|seat| game.seats.get(seat))
+            .map(|seat| format!(" to {}", seat.name))
+            .unwrap_or_default();
+        html! {
+            <p class="text-center">
+                { format!("Hand {} · Pass three cards {}{}", game.hand_number, direction, target) }
+            </p>
+        }
+    }
+
+    fn view_waiting(&self) -> Html {
+        let waiting_for = self
+            .props
+            .game
+            .seats
+            .iter()
+            .filter(|seat| !seat.passed)
+            .map(|seat| seat.name.clone())
+            .collect::<Vec<String>>()
+            .join(", ");
+        html! {
+            <div class="flex flex-col items-center">
+                <p>{ "You passed:" }</p>
+                <div class="flex flex-row">
+                    {
+                        for self.props.game.passed.iter().map(|card| {
+                            html! { <PlayingCard card=*card disabled=true /> }
+                        })
+                    }
+                </div>
+                <p>{ format!("Waiting for {}", waiting_for) }</p>
+            </div>
+        }
+    }
+
+    fn view_choosing(&self) -> Html {
+        let full = self.selected.len() == PASS_SIZE;
+        html! {
+            <div class="flex flex-col items-center">
+                <div class="flex flex-row flex-wrap justify-center">
+                    {
+                        for self.props.game.hand.iter().map(|card| {
+                            let selected = self.selected.contains(card);
+                            html! {
+                                <PlayingCard
+                                    card=*card
+                                    disabled=full && !selected
+                                    highlighted=selected
+                                    on_click=self.link.callback(Msg::Toggle)
+                                />
+                            }
+                        })
+                    }
+                </div>
+                <button
+                    class="w-32 m-4 disabled:opacity-50 bg-blue-200 hover:bg-blue-300 rounded-lg shadow-md"
+                    disabled=!full
+                    onclick=self.link.callback(|_| Msg::Confirm)
+                >
+                    { "Pass cards" }
+                </button>
+            </div>
+        }
+    }
+}
+
+impl Component for Passing {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
+        Self {
+            link,
+            props,
+            selected: vec![],
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Msg::Toggle(card) => {
+                if self.selected.contains(&card) {
+                    self.selected.retain(|&c| c != card);
+                } else if self.selected.len() < PASS_SIZE {
+                    self.selected.push(card);
+                }
+            }
+            Msg::Confirm => {
+                if self.selected.len() == PASS_SIZE {
+                    self.props.on_pass.emit(self.selected.clone());
+                }
+            }
+        }
+        true
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        // A new hand means a new choice.
+        if props.game.hand_number != self.props.game.hand_number {
+            self.selected.clear();
+        }
+        self.props.neq_assign(props)
+    }
+
+    fn view(&self) -> Html {
+        html! {
+            <div class="flex flex-col p-4 space-y-4 bg-green-700 rounded-lg">
+                { self.view_direction() }
+                {
+                    if self.props.game.passed.is_empty() {
+                        self.view_choosing()
+                    } else {
+                        self.view_waiting()
+                    }
+                }
+            </div>
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use game::{rules, Card, Phase, PlayedCard, Seat};
+use messages::Reaction;
+use yew::prelude::*;
+use yewtil::NeqAssign;
+
+use crate::components::playing_card::PlayingCard;
+
+#[derive(Clone, Debug, PartialEq, Properties)]
+pub struct Props {
+    pub game: messages::GameView,
+    /// Reactions to the trick to show next to the seats of whoever sent them.
+    pub reactions: Vec<messages::LobbyReactionResponse>,
+    pub on_play: Callback<Card>,
+    pub on_react: Callback<Reaction>,
+}
+
+/// The game as seen from the player's own seat, which is always at the bottom.
+pub struct Table {
+    props: Props,
+}
+
+impl Table {
+    /// The seat shown `offset` places clockwise from the player.
+    fn seat_at(&self, offset: usize) -> Seat {
+        (self.props.game.seat + offset) % game::PLAYERS
+    }
+
+    fn view_seat(&self, seat: Seat) -> Html {
+        let game = &self.props.game;
+        let view = match game.seats.get(seat) {
+            Some(view) => view,
+            None => return html! {},
+        };
+        let class = if game.turn == seat && game.phase == Phase::Playing {
+            "p-2 rounded-lg bg-yellow-200 shadow-md"
+        } else {
+            "p-2 rounded-lg bg-white shadow-md"
+        };
+        let reactions = self.props.reactions.iter().filter(|r| r.seat == Some(seat));
+        html! {
+            <div class="relative">
+                <div class=class>
+                    <p class="text-xs">{ game::SEAT_NAMES[seat] }</p>
+                    <p class="font-bold">{ view.name.clone() }</p>
+                    <p>{ format!("{} cards · {} points", view.cards_in_hand, view.score) }</p>
+                </div>
+                <div class="absolute -top-4 -right-4 flex flex-col space-y-1">
+                    {
+                        for reactions.map(|r| html! {
+                            <span class="px-2 bg-yellow-100 rounded-full shadow-md">
+                                { r.reaction.text() }
+                            </span>
+                        })
+                    }
+                </div>
+            </div>
+        }
+    }
+
+    fn view_reactions(&self) -> Html {
+        html! {
+            <div class="flex flex-row justify-center space-x-2">
+                {
+                    for Reaction::ALL.iter().map(|&reaction| {
+                        html! {
+                            <button
+                                class="px-2 bg-white hover:bg-yellow-100 rounded-full shadow-md"
+                                onclick=self.props.on_react.reform(move |_| reaction)
+                            >
+                                { reaction.text() }
+                            </button>
+                        }
+                    })
+                }
+            </div>
+        }
+    }
+
+    fn view_trick(&self) -> Html {
+        let game = &self.props.game;
+        // Leave the last trick on the table until someone leads to the next one.
+        let (trick, label) = if game.trick.is_empty() && !game.last_trick.is_empty() {
+            (&game.last_trick, "Last trick")
+        } else {
+            (&game.trick, "Current trick")
+        };
+        html! {
+            <div class="flex flex-col items-center">
+                <p class="text-sm">{ label }</p>
+                <div class="flex flex-row">
+                    { for trick.iter().map(|played| self.view_played(played)) }
+                </div>
+            </div>
+        }
+    }
+
+    fn view_played(&self, played: &PlayedCard) -> Html {
+        let name = self
+            .props
+            .game
+            .seats
+            .get(played.seat)
+            .map(|s| s.name.clone())
+            .unwrap_or_default();
+        html! {
+            <div class="flex flex-col items-center">
+                <PlayingCard card=played.card disabled=true />
+                <p class="text-xs">{ name }</p>
+            </div>
+        }
+    }
+
+    fn view_hand(&self) -> Html {
+        let game = &self.props.game;
+        let my_turn = game.turn == game.seat && game.phase == Phase::Playing;
+        let trick: Vec<Card> = game.trick.iter().map(|p| p.card).collect();
+        // The same rules the server enforces, so illegal cards can't be clicked.
+        let legal = rules::legal_plays(&game.hand, &trick, game.first_trick, game.hearts_broken);
+        html! {
+            <div class="flex flex-row flex-wrap justify-center">
+                {
+                    for game.hand.iter().map(|card| {
+                        html! {
+                            <PlayingCard
+                                card=*card
+                                disabled=!(my_turn && legal.contains(card))
+                                highlighted=game.first_trick && game.received.contains(card)
+                                on_click=self.props.on_play.clone()
+                            />
+                        }
+                    })
+                }
+            </div>
+        }
+    }
+
+    fn view_status(&self) -> Html {
+        let game = &self.props.game;
+        let status = match game.phase {
+            Phase::Passing => "Passing cards".to_owned(),
+            Phase::Finished => "Game over".to_owned(),
+            Phase::Playing if game.turn == game.seat => "Your turn".to_owned(),
+            Phase::Playing => match game.seats.get(game.turn) {
+                Some(seat) => format!("Waiting for {}", seat.name),
+                None => "Waiting".to_owned(),
+            },
+        };
+        html! {
+            <p class="text-center">
+                { format!("Hand {} · {}", game.hand_number, status) }
+                { if game.hearts_broken { " · Hearts are broken" } else { "" } }
+            </p>
+        }
+    }
+}
+
+impl Component for Table {
+    type Message = ();
+    type Properties = Props;
+
+    fn create(props: Self::Properties, _link: ComponentLink<Self>) -> Self {
+        Self { props }
+    }
+
+    fn update(&mut self, _msg: Self::Message) -> ShouldRender {
+        false
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        self.props.neq_assign(props)
+    }
+
+    fn view(&self) -> Html {
+        html! {
+            <div class="flex flex-col p-4 space-y-4 bg-green-700 rounded-lg">
+                <div class="flex justify-center">{ self.view_seat(self.seat_at(2)) }</div>
+                <div class="flex flex-row justify-between items-center">
+                    { self.view_seat(self.seat_at(1)) }
+                    { self.view_trick() }
+                    { self.view_seat(self.seat_at(3)) }
+                </div>
+                <div class="flex justify-center">{ self.view_seat(self.seat_at(0)) }</div>
+                { self.view_status() }
+                { self.view_reactions() }
+                { self.view_hand() }
+            </div>
+        }
+    }
+}
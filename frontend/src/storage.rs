@@ -0,0 +1,38 @@
+use serde_derive::{Deserialize, Serialize};
+use yew::format::Json;
+use yew::services::storage::{Area, StorageService};
+
+const KEY: &str = "hearts.saved";
+
+/// A place in a lobby that can be taken back with `messages::Resume`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    pub lobby_code: String,
+    pub session_token: String,
+}
+
+/// What the app remembers between visits.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Saved {
+    pub name: Option<String>,
+    pub session: Option<Session>,
+}
+
+pub fn load() -> Saved {
+    let storage = match StorageService::new(Area::Local) {
+        Ok(storage) => storage,
+        Err(e) => {
+            log::warn!("Local storage is unavailable: {}", e);
+            return Saved::default();
+        }
+    };
+    let Json(saved): Json<Result<Saved, anyhow::Error>> = storage.restore(KEY);
+    saved.unwrap_or_default()
+}
+
+pub fn save(saved: &Saved) {
+    match StorageService::new(Area::Local) {
+        Ok(mut storage) => storage.store(KEY, Json(saved)),
+        Err(e) => log::warn!("Local storage is unavailable: {}", e),
+    }
+}
@@ -0,0 +1,136 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Suit {
+    Clubs,
+    Diamonds,
+    Spades,
+    Hearts,
+}
+
+impl Suit {
+    pub const ALL: [Suit; 4] = [Suit::Clubs, Suit::Diamonds, Suit::Spades, Suit::Hearts];
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Suit::Clubs => "♣",
+            Suit::Diamonds => "♦",
+            Suit::Spades => "♠",
+            Suit::Hearts => "♥",
+        }
+    }
+
+    pub fn is_red(self) -> bool {
+        self == Suit::Diamonds || self == Suit::Hearts
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Rank {
+    Two,
+    Three,
+    Four,
+    Five,
+    Six,
+    Seven,
+    Eight,
+    Nine,
+    Ten,
+    Jack,
+    Queen,
+    King,
+    Ace,
+}
+
+impl Rank {
+    pub const ALL: [Rank; 13] = [
+        Rank::Two,
+        Rank::Three,
+        Rank::Four,
+        Rank::Five,
+        Rank::Six,
+        Rank::Seven,
+        Rank::Eight,
+        Rank::Nine,
+        Rank::Ten,
+        Rank::Jack,
+        Rank::Queen,
+        Rank::King,
+        Rank::Ace,
+    ];
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Rank::Two => "2",
+            Rank::Three => "3",
+            Rank::Four => "4",
+            Rank::Five => "5",
+            Rank::Six => "6",
+            Rank::Seven => "7",
+            Rank::Eight => "8",
+            Rank::Nine => "9",
+            Rank::Ten => "10",
+            Rank::Jack => "J",
+            Rank::Queen => "Q",
+            Rank::King => "K",
+            Rank::Ace => "A",
+        }
+    }
+}
+
+/// A playing card. Cards sort by suit and then by rank, which is the order a hand is shown in.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Card {
+    pub suit: Suit,
+    pub rank: Rank,
+}
+
+impl Card {
+    pub const TWO_OF_CLUBS: Card = Card {
+        suit: Suit::Clubs,
+        rank: Rank::Two,
+    };
+    pub const QUEEN_OF_SPADES: Card = Card {
+        suit: Suit::Spades,
+        rank: Rank::Queen,
+    };
+
+    pub fn new(
+        rank: Rank,
+        suit: Suit,
+    ) -> Self {
+        Self { suit, rank }
+    }
+
+    /// The penalty points for taking this card in a trick.
+    pub fn points(self) -> u32 {
+        if self.suit == Suit::Hearts {
+            1
+        } else if self == Card::QUEEN_OF_SPADES {
+            13
+        } else {
+            0
+        }
+    }
+}
+
+impl fmt::Display for Card {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        write!(f, "{}{}", self.rank.symbol(), self.suit.symbol())
+    }
+}
+
+/// All 52 cards, in order.
+pub fn deck() -> Vec<Card> {
+    Suit::ALL
+        .iter()
+        .flat_map(|&suit| Rank::ALL.iter().map(move |&rank| Card::new(rank, suit)))
+        .collect()
+}
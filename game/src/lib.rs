@@ -0,0 +1,442 @@
+use std::error::Error;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+pub mod cards;
+pub mod rules;
+
+pub use cards::{Card, Rank, Suit};
+
+/// Hearts is played by four players.
+pub const PLAYERS: usize = 4;
+/// The game ends after the hand where someone reaches this score.
+pub const SCORE_LIMIT: u32 = 100;
+
+/// A place at the table. Play goes clockwise, from each seat to the next.
+pub type Seat = usize;
+
+/// What each seat is called, so passing left goes from North to East.
+pub const SEAT_NAMES: [&str; PLAYERS] = ["North", "East", "South", "West"];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlayedCard {
+    pub seat: Seat,
+    pub card: Card,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    /// Everyone is choosing three cards to pass before the hand is played.
+    Passing,
+    Playing,
+    Finished,
+}
+
+/// Who each seat passes three cards to before a hand, which changes every hand.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PassDirection {
+    Left,
+    Right,
+    Across,
+    /// Every fourth hand nobody passes.
+    Hold,
+}
+
+impl PassDirection {
+    /// Hands go left, right, across then hold, starting from hand 1.
+    pub fn for_hand(hand_number: u32) -> Self {
+        match hand_number.saturating_sub(1) % 4 {
+            0 => PassDirection::Left,
+            1 => PassDirection::Right,
+            2 => PassDirection::Across,
+            _ => PassDirection::Hold,
+        }
+    }
+
+    /// The seat that `seat` passes to, if anyone.
+    pub fn target(
+        self,
+        seat: Seat,
+    ) -> Option<Seat> {
+        match self {
+            PassDirection::Left => Some((seat + 1) % PLAYERS),
+            PassDirection::Across => Some((seat + 2) % PLAYERS),
+            PassDirection::Right => Some((seat + PLAYERS - 1) % PLAYERS),
+            PassDirection::Hold => None,
+        }
+    }
+}
+
+/// How a finished hand was scored.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct HandResult {
+    pub hand_number: u32,
+    /// The points each seat scored this hand.
+    pub points: Vec<u32>,
+    /// The penalty cards each seat took this hand.
+    pub taken: Vec<Vec<Card>>,
+    /// The seat that took every point, if any.
+    pub moon: Option<Seat>,
+}
+
+/// The number of cards each player passes.
+pub const PASS_SIZE: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameError {
+    Finished,
+    Passing,
+    NotPassing,
+    AlreadyPassed,
+    InvalidPass,
+    NotYourTurn,
+    NotInHand,
+    IllegalPlay,
+}
+
+impl fmt::Display for GameError {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        let details = match self {
+            GameError::Finished => "The game is over",
+            GameError::Passing => "Cards are still being passed",
+            GameError::NotPassing => "Cards can't be passed right now",
+            GameError::AlreadyPassed => "You have already passed your cards",
+            GameError::InvalidPass => "Pass exactly three different cards from your hand",
+            GameError::NotYourTurn => "It is not your turn",
+            GameError::NotInHand => "That card is not in your hand",
+            GameError::IllegalPlay => "That card can't be played right now",
+        };
+        write!(f, "{}", details)
+    }
+}
+
+impl Error for GameError {}
+
+/// What playing a card led to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Played {
+    /// The card was added to the trick.
+    Card,
+    /// The card completed a trick, which was taken by `winner`.
+    Trick { winner: Seat },
+    /// The card completed the last trick of the hand and the scores have been updated.
+    Hand,
+}
+
+/// The state of a game of Hearts.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Game {
+    /// The cards held by each seat, sorted.
+    pub hands: Vec<Vec<Card>>,
+    /// The cards played to the current trick, in order.
+    pub trick: Vec<PlayedCard>,
+    /// The most recently completed trick, so it can still be shown after it is taken.
+    pub last_trick: Vec<PlayedCard>,
+    pub turn: Seat,
+    pub hearts_broken: bool,
+    /// The number of tricks taken so far this hand.
+    pub tricks_played: usize,
+    /// The point cards each seat has taken this hand.
+    pub taken: Vec<Vec<Card>>,
+    /// The total score of each seat over every hand.
+    pub scores: Vec<u32>,
+    /// Counts from 1.
+    pub hand_number: u32,
+    pub phase: Phase,
+    /// The cards each seat has chosen to pass this hand, empty until they choose.
+    #[serde(default)]
+    pub passes: Vec<Vec<Card>>,
+    /// The cards each seat was passed this hand.
+    #[serde(default)]
+    pub received: Vec<Vec<Card>>,
+    /// Every finished hand, in order.
+    #[serde(default)]
+    pub history: Vec<HandResult>,
+}
+
+impl Game {
+    /// Start a game, dealing the first hand from a shuffled `deck`.
+    pub fn new(deck: Vec<Card>) -> Self {
+        let mut game = Self {
+            hands: vec![vec![]; PLAYERS],
+            trick: vec![],
+            last_trick: vec![],
+            turn: 0,
+            hearts_broken: false,
+            tricks_played: 0,
+            taken: vec![vec![]; PLAYERS],
+            scores: vec![0; PLAYERS],
+            hand_number: 0,
+            phase: Phase::Playing,
+            passes: vec![vec![]; PLAYERS],
+            received: vec![vec![]; PLAYERS],
+            history: vec![],
+        };
+        game.deal(deck);
+        game
+    }
+
+    /// Deal the next hand from a shuffled `deck`.
+    pub fn deal(
+        &mut self,
+        deck: Vec<Card>,
+    ) {
+        self.hands = vec![vec![]; PLAYERS];
+        for (i, card) in deck.into_iter().enumerate() {
+            self.hands[i % PLAYERS].push(card);
+        }
+        for hand in self.hands.iter_mut() {
+            hand.sort();
+        }
+        self.trick.clear();
+        self.last_trick.clear();
+        self.taken = vec![vec![]; PLAYERS];
+        self.hearts_broken = false;
+        self.tricks_played = 0;
+        self.hand_number += 1;
+        self.passes = vec![vec![]; PLAYERS];
+        self.received = vec![vec![]; PLAYERS];
+        if self.pass_direction() == PassDirection::Hold {
+            self.start_playing();
+        } else {
+            self.phase = Phase::Passing;
+        }
+    }
+
+    fn start_playing(&mut self) {
+        self.phase = Phase::Playing;
+        self.turn = self
+            .hands
+            .iter()
+            .position(|hand| hand.contains(&Card::TWO_OF_CLUBS))
+            .unwrap_or(0);
+    }
+
+    pub fn pass_direction(&self) -> PassDirection {
+        PassDirection::for_hand(self.hand_number)
+    }
+
+    pub fn has_passed(
+        &self,
+        seat: Seat,
+    ) -> bool {
+        !self.passes[seat].is_empty()
+    }
+
+    /// Choose the cards `seat` passes, exchanging everyone's once the last seat has chosen.
+    ///
+    /// Returns whether the cards were exchanged and the hand can be played.
+    pub fn pass(
+        &mut self,
+        seat: Seat,
+        cards: Vec<Card>,
+    ) -> Result<bool, GameError> {
+        if self.phase != Phase::Passing {
+            return Err(GameError::NotPassing);
+        }
+        if self.has_passed(seat) {
+            return Err(GameError::AlreadyPassed);
+        }
+        let mut chosen = cards;
+        chosen.sort();
+        chosen.dedup();
+        if chosen.len() != PASS_SIZE || !chosen.iter().all(|c| self.hands[seat].contains(c)) {
+            return Err(GameError::InvalidPass);
+        }
+
+        self.hands[seat].retain(|c| !chosen.contains(c));
+        self.passes[seat] = chosen;
+        if !(0..PLAYERS).all(|seat| self.has_passed(seat)) {
+            return Ok(false);
+        }
+
+        let direction = self.pass_direction();
+        for from in 0..PLAYERS {
+            if let Some(to) = direction.target(from) {
+                let cards = self.passes[from].clone();
+                self.hands[to].extend(cards.iter().copied());
+                self.hands[to].sort();
+                self.received[to] = cards;
+            }
+        }
+        self.start_playing();
+        Ok(true)
+    }
+
+    pub fn is_first_trick(&self) -> bool {
+        self.tricks_played == 0
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.phase == Phase::Finished
+    }
+
+    /// The cards `seat` may play, which is none unless it is their turn.
+    pub fn legal_plays(
+        &self,
+        seat: Seat,
+    ) -> Vec<Card> {
+        if self.phase != Phase::Playing || seat != self.turn {
+            return vec![];
+        }
+        let trick: Vec<Card> = self.trick.iter().map(|p| p.card).collect();
+        rules::legal_plays(
+            &self.hands[seat],
+            &trick,
+            self.is_first_trick(),
+            self.hearts_broken,
+        )
+    }
+
+    pub fn play(
+        &mut self,
+        seat: Seat,
+        card: Card,
+    ) -> Result<Played, GameError> {
+        match self.phase {
+            Phase::Playing => {}
+            Phase::Passing => return Err(GameError::Passing),
+            Phase::Finished => return Err(GameError::Finished),
+        }
+        if seat != self.turn {
+            return Err(GameError::NotYourTurn);
+        }
+        if !self.hands[seat].contains(&card) {
+            return Err(GameError::NotInHand);
+        }
+        if !self.legal_plays(seat).contains(&card) {
+            return Err(GameError::IllegalPlay);
+        }
+
+        self.hands[seat].retain(|&c| c != card);
+        self.trick.push(PlayedCard { seat, card });
+        if card.suit == Suit::Hearts {
+            self.hearts_broken = true;
+        }
+
+        if self.trick.len() < PLAYERS {
+            self.turn = (seat + 1) % PLAYERS;
+            return Ok(Played::Card);
+        }
+
+        let cards: Vec<Card> = self.trick.iter().map(|p| p.card).collect();
+        let winner = self.trick[rules::trick_winner(&cards)].seat;
+        self.taken[winner].extend(cards.into_iter().filter(|c| c.points() > 0));
+        self.last_trick = std::mem::take(&mut self.trick);
+        self.tricks_played += 1;
+        self.turn = winner;
+
+        if !self.hands[winner].is_empty() {
+            return Ok(Played::Trick { winner });
+        }
+
+        let points = rules::score_hand(&self.taken);
+        for (score, points) in self.scores.iter_mut().zip(points.iter()) {
+            *score += points;
+        }
+        self.history.push(HandResult {
+            hand_number: self.hand_number,
+            points,
+            taken: self.taken.clone(),
+            moon: rules::moon_shooter(&self.taken),
+        });
+        if self.scores.iter().any(|&score| score >= SCORE_LIMIT) {
+            self.phase = Phase::Finished;
+        }
+        Ok(Played::Hand)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every seat passes their three highest cards.
+    fn pass_all(game: &mut Game) {
+        for seat in 0..PLAYERS {
+            let cards = game.hands[seat][10..].to_vec();
+            game.pass(seat, cards).expect("pass was rejected");
+        }
+    }
+
+    #[test]
+    fn two_of_clubs_starts() {
+        let mut game = Game::new(cards::deck());
+        pass_all(&mut game);
+        // Dealing an unshuffled deck round the table gives the first seat the two of clubs.
+        assert_eq!(game.turn, 0);
+        assert_eq!(game.legal_plays(0), vec![Card::TWO_OF_CLUBS]);
+        assert_eq!(game.legal_plays(1), vec![]);
+    }
+
+    #[test]
+    fn rejects_play_out_of_turn() {
+        let mut game = Game::new(cards::deck());
+        pass_all(&mut game);
+        let card = game.hands[1][0];
+        assert_eq!(game.play(1, card), Err(GameError::NotYourTurn));
+    }
+
+    #[test]
+    fn playing_out_a_hand_scores_it() {
+        let mut game = Game::new(cards::deck());
+        pass_all(&mut game);
+        let mut played = Played::Card;
+        for _ in 0..52 {
+            let seat = game.turn;
+            let card = game.legal_plays(seat)[0];
+            played = game.play(seat, card).expect("legal play was rejected");
+        }
+
+        assert_eq!(played, Played::Hand);
+        assert!(game.hands.iter().all(|hand| hand.is_empty()));
+        let total: u32 = game.scores.iter().sum();
+        assert!(total == rules::MOON_POINTS || total == 3 * rules::MOON_POINTS);
+        assert_eq!(game.history.len(), 1);
+        assert_eq!(game.history[0].points, game.scores);
+
+        game.deal(cards::deck());
+        assert_eq!(game.hand_number, 2);
+        assert_eq!(game.phase, Phase::Passing);
+        assert!(game.hands.iter().all(|hand| hand.len() == 13));
+    }
+
+    #[test]
+    fn passing_exchanges_cards_once_everyone_has_chosen() {
+        let mut game = Game::new(cards::deck());
+        assert_eq!(game.pass_direction(), PassDirection::Left);
+        let card = game.hands[0][0];
+        assert_eq!(game.play(0, card), Err(GameError::Passing));
+        assert_eq!(
+            game.pass(0, vec![card, card, card]),
+            Err(GameError::InvalidPass)
+        );
+
+        let chosen = game.hands[0][..3].to_vec();
+        assert_eq!(game.pass(0, chosen.clone()), Ok(false));
+        assert_eq!(game.pass(0, chosen.clone()), Err(GameError::AlreadyPassed));
+        for seat in 1..PLAYERS {
+            let cards = game.hands[seat][..3].to_vec();
+            assert_eq!(game.pass(seat, cards), Ok(seat == PLAYERS - 1));
+        }
+
+        assert_eq!(game.phase, Phase::Playing);
+        assert_eq!(game.received[1], chosen);
+        assert!(chosen.iter().all(|c| game.hands[1].contains(c)));
+        assert!(game.hands.iter().all(|hand| hand.len() == 13));
+    }
+
+    #[test]
+    fn every_fourth_hand_holds() {
+        assert_eq!(PassDirection::for_hand(3).target(0), Some(2));
+        assert_eq!(PassDirection::for_hand(2).target(0), Some(3));
+        assert_eq!(PassDirection::for_hand(4), PassDirection::Hold);
+        assert_eq!(PassDirection::for_hand(5), PassDirection::Left);
+    }
+}
@@ -0,0 +1,171 @@
+//! The rules of Hearts which don't depend on the state of a whole game.
+//!
+//! These are shared by the server, which enforces them, and the frontend, which uses them to show
+//! which cards can be played.
+
+use crate::cards::{Card, Suit};
+
+/// Points shared between the players each hand, which the moon shooter gives to everyone else.
+pub const MOON_POINTS: u32 = 26;
+
+/// The cards in `hand` which may be played next.
+///
+/// `trick` holds the cards already played to the current trick, in the order they were played.
+pub fn legal_plays(
+    hand: &[Card],
+    trick: &[Card],
+    first_trick: bool,
+    hearts_broken: bool,
+) -> Vec<Card> {
+    let allowed: Vec<Card> = match trick.first() {
+        // The holder of the two of clubs leads it to the first trick.
+        None if first_trick => hand
+            .iter()
+            .copied()
+            .filter(|&c| c == Card::TWO_OF_CLUBS)
+            .collect(),
+        // Hearts can't be led until one has been played, unless there is nothing else to lead.
+        None if !hearts_broken => hand
+            .iter()
+            .copied()
+            .filter(|c| c.suit != Suit::Hearts)
+            .collect(),
+        None => hand.to_vec(),
+        Some(lead) => {
+            let following: Vec<Card> = hand
+                .iter()
+                .copied()
+                .filter(|c| c.suit == lead.suit)
+                .collect();
+            if !following.is_empty() {
+                following
+            } else if first_trick {
+                // No points can be thrown away on the first trick, when there is a choice.
+                hand.iter().copied().filter(|c| c.points() == 0).collect()
+            } else {
+                hand.to_vec()
+            }
+        }
+    };
+
+    if allowed.is_empty() {
+        hand.to_vec()
+    } else {
+        allowed
+    }
+}
+
+/// The index of the card in a complete trick which takes it.
+pub fn trick_winner(trick: &[Card]) -> usize {
+    let lead = trick[0].suit;
+    trick
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| c.suit == lead)
+        .max_by_key(|(_, c)| c.rank)
+        .map(|(i, _)| i)
+        .unwrap_or(0)
+}
+
+/// The player who took every point in a hand, if anyone did.
+pub fn moon_shooter(taken: &[Vec<Card>]) -> Option<usize> {
+    taken
+        .iter()
+        .position(|cards| cards.iter().map(|c| c.points()).sum::<u32>() == MOON_POINTS)
+}
+
+/// The score for a hand, from the cards each player took.
+///
+/// A player who took every point "shoots the moon" and everybody else scores 26 instead.
+pub fn score_hand(taken: &[Vec<Card>]) -> Vec<u32> {
+    let points: Vec<u32> = taken
+        .iter()
+        .map(|cards| cards.iter().map(|c| c.points()).sum())
+        .collect();
+
+    match moon_shooter(taken) {
+        Some(shooter) => (0..points.len())
+            .map(|seat| if seat == shooter { 0 } else { MOON_POINTS })
+            .collect(),
+        None => points,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cards::Rank;
+
+    fn card(
+        rank: Rank,
+        suit: Suit,
+    ) -> Card {
+        Card::new(rank, suit)
+    }
+
+    #[test]
+    fn two_of_clubs_leads_the_first_trick() {
+        let hand = vec![card(Rank::Two, Suit::Clubs), card(Rank::Ace, Suit::Clubs)];
+        assert_eq!(
+            legal_plays(&hand, &[], true, false),
+            vec![Card::TWO_OF_CLUBS]
+        );
+    }
+
+    #[test]
+    fn must_follow_suit() {
+        let hand = vec![card(Rank::Five, Suit::Clubs), card(Rank::Ace, Suit::Hearts)];
+        let trick = vec![card(Rank::King, Suit::Clubs)];
+        assert_eq!(
+            legal_plays(&hand, &trick, false, false),
+            vec![card(Rank::Five, Suit::Clubs)]
+        );
+    }
+
+    #[test]
+    fn no_points_on_the_first_trick() {
+        let hand = vec![
+            Card::QUEEN_OF_SPADES,
+            card(Rank::Ace, Suit::Hearts),
+            card(Rank::Three, Suit::Diamonds),
+        ];
+        assert_eq!(
+            legal_plays(&hand, &[Card::TWO_OF_CLUBS], true, false),
+            vec![card(Rank::Three, Suit::Diamonds)]
+        );
+    }
+
+    #[test]
+    fn hearts_cannot_be_led_until_broken() {
+        let hand = vec![
+            card(Rank::Ace, Suit::Hearts),
+            card(Rank::Three, Suit::Diamonds),
+        ];
+        assert_eq!(
+            legal_plays(&hand, &[], false, false),
+            vec![card(Rank::Three, Suit::Diamonds)]
+        );
+        assert_eq!(legal_plays(&hand, &[], false, true), hand);
+
+        let only_hearts = vec![card(Rank::Ace, Suit::Hearts)];
+        assert_eq!(legal_plays(&only_hearts, &[], false, false), only_hearts);
+    }
+
+    #[test]
+    fn highest_card_of_the_lead_suit_takes_the_trick() {
+        let trick = vec![
+            card(Rank::Ten, Suit::Diamonds),
+            card(Rank::Ace, Suit::Spades),
+            card(Rank::Queen, Suit::Diamonds),
+            card(Rank::Two, Suit::Diamonds),
+        ];
+        assert_eq!(trick_winner(&trick), 2);
+    }
+
+    #[test]
+    fn shooting_the_moon_gives_everyone_else_the_points() {
+        let mut taken = vec![vec![], vec![], vec![], vec![]];
+        taken[1] = crate::cards::deck();
+        assert_eq!(score_hand(&taken), vec![26, 0, 26, 26]);
+    }
+}
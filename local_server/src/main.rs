@@ -0,0 +1,139 @@
+use std::env;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use chrono::Utc;
+use futures::{FutureExt, StreamExt};
+use nanoid::nanoid;
+use serde_json::Value;
+use warp::ws::{Message, WebSocket, Ws};
+use warp::Filter;
+
+use common::lobby::{InMemoryLobbyRepo, LobbyRepo};
+use common::router;
+use common::transport::LocalTransport;
+
+mod store;
+use store::FileLobbyRepo;
+
+#[derive(Clone)]
+struct Server {
+    repo: Arc<dyn LobbyRepo>,
+    /// Stands in for `post_to_connection` on the API Gateway management API.
+    transport: LocalTransport,
+}
+
+#[tokio::main]
+async fn main() {
+    env_logger::init();
+
+    let addr: SocketAddr = env::var("LOCAL_SERVER_ADDR")
+        .unwrap_or_else(|_| "127.0.0.1:9001".to_owned())
+        .parse()
+        .expect("LOCAL_SERVER_ADDR is not a valid socket address");
+
+    let repo: Arc<dyn LobbyRepo> = match env::var("LOCAL_STORE_FILE") {
+        Ok(path) => {
+            log::info!("Storing lobbies in {}", path);
+            Arc::new(FileLobbyRepo::open(path).expect("Could not open LOCAL_STORE_FILE"))
+        }
+        Err(_) => {
+            log::info!("Storing lobbies in memory");
+            Arc::new(InMemoryLobbyRepo::new())
+        }
+    };
+
+    let server = Server {
+        repo,
+        transport: LocalTransport::new(),
+    };
+    let with_server = warp::any().map(move || server.clone());
+
+    let websocket = warp::path::end()
+        .and(warp::ws())
+        .and(with_server)
+        .map(|ws: Ws, server: Server| ws.on_upgrade(move |socket| connected(socket, server)));
+
+    log::info!("Listening on ws://{}", addr);
+    warp::serve(websocket).run(addr).await;
+}
+
+/// Emulates the `$connect` and `$disconnect` routes around the lifetime of a socket.
+async fn connected(
+    socket: WebSocket,
+    server: Server,
+) {
+    let connection_id = nanoid!(16);
+    log::info!("connecting {}", connection_id);
+
+    let (ws_tx, mut ws_rx) = socket.split();
+    let posted = server.transport.connect(&connection_id);
+    tokio::task::spawn(
+        posted
+            .map(|text| Ok::<_, warp::Error>(Message::text(text)))
+            .forward(ws_tx)
+            .map(|result| {
+                if let Err(e) = result {
+                    log::error!("websocket send error: {}", e);
+                }
+            }),
+    );
+
+    while let Some(result) = ws_rx.next().await {
+        let message = match result {
+            Ok(message) => message,
+            Err(e) => {
+                log::error!("websocket error ({}): {}", connection_id, e);
+                break;
+            }
+        };
+        // Only text frames carry actions. Pings, pongs and close frames are ignored.
+        if let Ok(body) = message.to_str() {
+            route(&server, &connection_id, &body.to_owned()).await;
+        }
+    }
+
+    log::info!("disconnecting {}", connection_id);
+    server.transport.disconnect(&connection_id);
+    let ctx = router::Context {
+        repo: &*server.repo,
+        transport: &server.transport,
+        connection_id: &connection_id,
+        now: Utc::now(),
+    };
+    if let Err(e) = router::disconnect(&ctx).await {
+        log::error!("{:?}", e);
+    }
+}
+
+/// Pick a handler like API Gateway does with the `$request.body.action` route selection
+/// expression, falling back to `$default`.
+async fn route(
+    server: &Server,
+    connection_id: &String,
+    body: &str,
+) {
+    log::info!("recv {}", body);
+    let action = serde_json::from_str::<Value>(body)
+        .ok()
+        .and_then(|v| v.get("action").and_then(Value::as_str).map(str::to_owned));
+
+    let result = match action.as_deref() {
+        Some("send") | Some("hearts") => {
+            let ctx = router::Context {
+                repo: &*server.repo,
+                transport: &server.transport,
+                connection_id,
+                now: Utc::now(),
+            };
+            router::route(&ctx, body).await
+        }
+        _ => {
+            let error = router::unroutable(body);
+            router::reject(&server.transport, connection_id, body, error).await
+        }
+    };
+    if let Err(e) = result {
+        log::error!("{:?}", e);
+    }
+}
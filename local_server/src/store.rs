@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use common::lobby::{Lobby, LobbyId, LobbyRepo, LobbyServiceError};
+
+/// A `LobbyRepo` which writes every Lobby to a single JSON file so they survive a restart.
+pub struct FileLobbyRepo {
+    path: PathBuf,
+    lobbies: Mutex<HashMap<LobbyId, Lobby>>,
+    /// Not written to the file, since no connection outlives the server.
+    connections: Mutex<HashMap<String, LobbyId>>,
+}
+
+impl FileLobbyRepo {
+    /// Open the store at `path`, starting empty if the file does not exist yet.
+    pub fn open(
+        path: impl Into<PathBuf>
+    ) -> Result<Self, Box<dyn std::error::Error + Sync + Send + 'static>> {
+        let path = path.into();
+        let lobbies = match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self {
+            path,
+            lobbies: Mutex::new(lobbies),
+            connections: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn persist(
+        &self,
+        lobbies: &HashMap<LobbyId, Lobby>,
+    ) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
+        fs::write(&self.path, serde_json::to_string_pretty(lobbies)?)?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl LobbyRepo for FileLobbyRepo {
+    async fn get(
+        &self,
+        lobby_id: &LobbyId,
+    ) -> Result<Option<Lobby>, Box<dyn std::error::Error + Sync + Send + 'static>> {
+        let lobbies = self.lobbies.lock().unwrap();
+        Ok(lobbies.get(lobby_id).cloned())
+    }
+
+    async fn create(
+        &self,
+        lobby: &Lobby,
+    ) -> Result<bool, Box<dyn std::error::Error + Sync + Send + 'static>> {
+        let mut lobbies = self.lobbies.lock().unwrap();
+        if lobbies.contains_key(&lobby.id) {
+            return Ok(false);
+        }
+        lobbies.insert(lobby.id.clone(), lobby.clone());
+        self.persist(&lobbies)?;
+        Ok(true)
+    }
+
+    async fn update(
+        &self,
+        lobby_id: &LobbyId,
+        previous_updated_at: &DateTime<Utc>,
+        lobby: &Lobby,
+    ) -> Result<Lobby, Box<dyn std::error::Error + Sync + Send + 'static>> {
+        let mut lobbies = self.lobbies.lock().unwrap();
+        match lobbies.get(lobby_id) {
+            Some(current) if &current.updated_at == previous_updated_at => {
+                lobbies.insert(lobby_id.clone(), lobby.clone());
+                self.persist(&lobbies)?;
+                Ok(lobby.clone())
+            }
+            _ => Err(LobbyServiceError::conflict().into()),
+        }
+    }
+
+    async fn set_connection_lobby(
+        &self,
+        connection_id: &str,
+        lobby_id: &LobbyId,
+    ) -> Result<(), Box<dyn std::error::Error + Sync + Send + 'static>> {
+        let mut connections = self.connections.lock().unwrap();
+        connections.insert(connection_id.to_string(), lobby_id.clone());
+        Ok(())
+    }
+
+    async fn connection_lobby(
+        &self,
+        connection_id: &str,
+    ) -> Result<Option<LobbyId>, Box<dyn std::error::Error + Sync + Send + 'static>> {
+        let connections = self.connections.lock().unwrap();
+        Ok(connections.get(connection_id).cloned())
+    }
+}
@@ -1,9 +1,26 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+pub use game::{Card, HandResult, PassDirection, Phase, PlayedCard, Rank, Suit};
+
+/// The most characters a chat message can have.
+pub const CHAT_MAX_LENGTH: usize = 500;
+/// The most characters a player's name can have, before any number added to tell it apart.
+pub const NAME_MAX_LENGTH: usize = 20;
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Player {
     pub name: String,
     pub connection_id: String,
+    /// Whether the host has stopped this player from chatting.
+    #[serde(default)]
+    pub muted: bool,
+    /// Whether the player's connection has closed and they have not come back yet.
+    #[serde(default)]
+    pub away: bool,
+    /// Whether the player is ready for the next game to be dealt.
+    #[serde(default)]
+    pub ready: bool,
 }
 
 pub type LobbyId = String;
@@ -12,51 +29,393 @@ pub type LobbyId = String;
 pub struct Lobby {
     pub id: LobbyId,
     pub players: Vec<Player>,
+    /// The connection id of the player who runs the lobby.
+    #[serde(default)]
+    pub host: String,
+    /// Whether the host has stopped anyone else from joining.
+    #[serde(default)]
+    pub locked: bool,
+    /// The connection id of the player in each seat, clockwise from North.
+    #[serde(default)]
+    pub seats: Vec<Option<String>>,
+    /// Whether a game is dealt as soon as everyone at the table is ready.
+    #[serde(default)]
+    pub auto_start: bool,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct LobbyActionCreate {
     pub name: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct LobbyActionCreateResponse {
     pub lobby: Lobby,
+    /// The player this response was sent to.
+    pub you: Player,
+    /// The secret the player this response was sent to can `Resume` with.
+    pub session_token: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct LobbyActionJoin {
     pub name: String,
     pub lobby_code: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct LobbyActionJoinResponse {
     pub lobby: Lobby,
+    /// The player this response was sent to.
+    pub you: Player,
+    /// The secret the player this response was sent to can `Resume` with.
+    pub session_token: String,
+    /// The latest chat messages in the lobby, oldest first.
+    #[serde(default)]
+    pub history: Vec<LobbyMessageResponse>,
+    /// Whether there are older chat messages than `history` to ask for.
+    #[serde(default)]
+    pub more_history: bool,
+}
+
+/// Sent by the host to stop the player called `name` from chatting, or to let them again.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct LobbyActionMute {
+    pub lobby_code: String,
+    pub name: String,
+    pub muted: bool,
+}
+
+/// Sent by the host to remove the player called `name` from the lobby.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct LobbyActionKick {
+    pub lobby_code: String,
+    pub name: String,
+}
+
+/// Sent by the host to make the player called `name` the host instead.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct LobbyActionTransferHost {
+    pub lobby_code: String,
+    pub name: String,
+}
+
+/// Sent by the host to stop anyone else joining the lobby, or to let them again.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct LobbyActionLock {
+    pub lobby_code: String,
+    pub locked: bool,
+}
+
+/// Sent by a player to sit in an open seat, moving from their old one if they had one.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct LobbyActionTakeSeat {
+    pub lobby_code: String,
+    pub seat: usize,
+}
+
+/// Sent by the host to swap whoever is sitting in two seats, either of which may be open.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct LobbyActionSwapSeats {
+    pub lobby_code: String,
+    pub first: usize,
+    pub second: usize,
+}
+
+/// Sent by a player at the table when they are ready for the next game, or no longer are.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct LobbyActionReady {
+    pub lobby_code: String,
+    pub ready: bool,
+}
+
+/// Sent by the host to deal games as soon as everyone at the table is ready, or to wait for a
+/// start action again.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct LobbyActionAutoStart {
+    pub lobby_code: String,
+    pub auto_start: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct LobbyActionLeave {
+    pub lobby_code: String,
+}
+
+/// Sent to every player when something about the lobby changes.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct LobbyUpdate {
+    pub lobby: Lobby,
+}
+
+/// Sent from a new connection, like after a refresh, to take back a place in a lobby.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Resume {
+    pub lobby_code: String,
+    pub session_token: String,
+}
+
+/// The lobby the player resumed, followed by the game state if a game is being played.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct ResumeResponse {
+    pub lobby: Lobby,
+    pub you: Player,
+    /// The latest chat messages in the lobby, oldest first.
+    #[serde(default)]
+    pub history: Vec<LobbyMessageResponse>,
+    /// Whether there are older chat messages than `history` to ask for.
+    #[serde(default)]
+    pub more_history: bool,
+}
+
+/// Who a chat message is for, when it isn't for everyone in the lobby.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Recipient {
+    /// Only the player with this name.
+    Player { name: String },
+    /// Only the sender's partner, who sits across the table from them.
+    Team,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct LobbyMessage {
     pub lobby_code: String,
     pub body: String,
+    /// Picked by the sender so it can match the echoed message to the one it is showing.
+    #[serde(default)]
+    pub client_id: Option<String>,
+    /// Everyone in the lobby sees the message without one.
+    #[serde(default)]
+    pub to: Option<Recipient>,
 }
 
+/// A chat message as confirmed by the server, which is also echoed back to its sender.
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct LobbyMessageResponse {
+    pub id: String,
+    pub sent_at: DateTime<Utc>,
     pub name: String,
     pub body: String,
+    /// The sender's `client_id` for the message, if it gave one.
+    pub client_id: Option<String>,
+    #[serde(default)]
+    pub to: Option<Recipient>,
+    /// Whether the server said this rather than a player, for messages in chat history which
+    /// were first sent as a `SystemMessage`.
+    #[serde(default)]
+    pub system: bool,
+}
+
+/// Something the server says in the chat itself, like who joined or a command's answer.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct SystemMessage {
+    pub id: String,
+    pub sent_at: DateTime<Utc>,
+    pub body: String,
+}
+
+/// Canned table talk, for when there is no time to type.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Reaction {
+    Nice,
+    Ouch,
+    Peek,
+    Gg,
+}
+
+impl Reaction {
+    pub const ALL: [Reaction; 4] = [Reaction::Nice, Reaction::Ouch, Reaction::Peek, Reaction::Gg];
+
+    pub fn text(self) -> &'static str {
+        match self {
+            Reaction::Nice => "nice!",
+            Reaction::Ouch => "ouch",
+            Reaction::Peek => "🫣",
+            Reaction::Gg => "gg",
+        }
+    }
+}
+
+/// A reaction to the current trick, or to the chat message with id `message_id`.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct LobbyReaction {
+    pub lobby_code: String,
+    pub reaction: Reaction,
+    #[serde(default)]
+    pub message_id: Option<String>,
+}
+
+/// A reaction sent to everyone in the lobby, which is shown for a moment and not kept.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct LobbyReactionResponse {
+    pub id: String,
+    pub name: String,
+    /// The seat at the table of whoever reacted, if they have one.
+    pub seat: Option<usize>,
+    pub reaction: Reaction,
+    pub message_id: Option<String>,
+}
+
+/// Asks for the page of chat messages sent before the message with id `before`.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct LobbyMessageHistory {
+    pub lobby_code: String,
+    pub before: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct LobbyMessageHistoryResponse {
+    pub lobby_code: String,
+    /// Oldest first.
+    pub messages: Vec<LobbyMessageResponse>,
+    /// Whether there are older chat messages still.
+    pub more: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct GameActionStart {
+    pub lobby_code: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct GameActionPlay {
+    pub lobby_code: String,
+    pub card: Card,
+}
+
+/// The three cards a player passes before the hand is played.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct GameActionPass {
+    pub lobby_code: String,
+    pub cards: Vec<Card>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct SeatView {
+    pub name: String,
+    pub cards_in_hand: usize,
+    pub score: u32,
+    /// Whether they have chosen their cards to pass this hand.
+    pub passed: bool,
+}
+
+/// A game as one player sees it, which leaves out the other players' hands.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct GameView {
+    /// The seat of the player this view is for.
+    pub seat: usize,
+    /// Every seat at the table, clockwise.
+    pub seats: Vec<SeatView>,
+    pub hand: Vec<Card>,
+    pub trick: Vec<PlayedCard>,
+    pub last_trick: Vec<PlayedCard>,
+    pub turn: usize,
+    pub first_trick: bool,
+    pub hearts_broken: bool,
+    pub hand_number: u32,
+    pub phase: Phase,
+    pub pass_direction: PassDirection,
+    /// The cards this player chose to pass, empty until they choose.
+    pub passed: Vec<Card>,
+    /// The cards this player was passed this hand.
+    pub received: Vec<Card>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct GameUpdate {
+    pub game: GameView,
+}
+
+/// The scores of every hand so far, sent when a game starts and whenever a hand is scored.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct ScoreUpdate {
+    pub lobby_code: String,
+    /// The name of each seat, clockwise.
+    pub players: Vec<String>,
+    pub hands: Vec<HandResult>,
+    pub totals: Vec<u32>,
+    pub finished: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// The `action` or `type` of the message is not one the server handles.
+    UnknownAction,
+    /// The message is not valid JSON.
+    MalformedJson,
+    /// The message is missing fields or has fields of the wrong type.
+    InvalidMessage,
+    /// The lobby in the message doesn't exist.
+    LobbyNotFound,
+    /// The host has stopped anyone else from joining the lobby.
+    LobbyLocked,
+    /// The name can't be used, like an empty one or one with characters that aren't allowed.
+    InvalidName,
+    /// Nobody in the lobby matches the player in the message.
+    PlayerNotFound,
+    /// The message can't be acted on right now, like starting a game without enough players.
+    InvalidAction,
+    /// A game can't be started until everyone at the table is ready.
+    NotReady,
+    /// The card can't be played, or the cards can't be passed.
+    IllegalPlay,
+    /// The chat message is longer than `CHAT_MAX_LENGTH`.
+    MessageTooLong,
+    /// Too many chat messages were sent too quickly.
+    RateLimited,
+    /// The host has muted the sender.
+    Muted,
+    /// The chat message contains a word the server doesn't allow.
+    BlockedWord,
+    /// Something else changed at the same time, so sending the message again should work.
+    TryAgain,
+}
+
+/// Sent to a connection when the server could not act on something it sent.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct ErrorResponse {
+    pub kind: ErrorKind,
+    pub message: String,
+    /// Where in the payload the problem was found, like `type`.
+    pub path: Option<String>,
 }
 
 /// the structure of the client payload (action aside)
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum Message {
     LobbyActionCreate(LobbyActionCreate),
     LobbyActionCreateResponse(LobbyActionCreateResponse),
     LobbyActionJoin(LobbyActionJoin),
     LobbyActionJoinResponse(LobbyActionJoinResponse),
+    LobbyActionMute(LobbyActionMute),
+    LobbyActionKick(LobbyActionKick),
+    LobbyActionTransferHost(LobbyActionTransferHost),
+    LobbyActionLock(LobbyActionLock),
+    LobbyActionTakeSeat(LobbyActionTakeSeat),
+    LobbyActionSwapSeats(LobbyActionSwapSeats),
+    LobbyActionReady(LobbyActionReady),
+    LobbyActionAutoStart(LobbyActionAutoStart),
+    LobbyActionLeave(LobbyActionLeave),
+    LobbyUpdate(LobbyUpdate),
+    Resume(Resume),
+    ResumeResponse(ResumeResponse),
     LobbyMessage(LobbyMessage),
     LobbyMessageResponse(LobbyMessageResponse),
+    SystemMessage(SystemMessage),
+    LobbyReaction(LobbyReaction),
+    LobbyReactionResponse(LobbyReactionResponse),
+    LobbyMessageHistory(LobbyMessageHistory),
+    LobbyMessageHistoryResponse(LobbyMessageHistoryResponse),
+    GameActionStart(GameActionStart),
+    GameActionPlay(GameActionPlay),
+    GameActionPass(GameActionPass),
+    GameUpdate(GameUpdate),
+    ScoreUpdate(ScoreUpdate),
+    Error(ErrorResponse),
 }
 
 // #[cfg(test)]